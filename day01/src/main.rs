@@ -1,46 +1,433 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead, Error};
-use std::path::Path;
+use std::io::{self, BufRead, Error, ErrorKind, Write};
 
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+/// The food items carried by one elf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Group {
+    calories: u64,
+    items: usize,
 }
 
-fn load_calories(path: &str) -> Result<Vec<i32>, Error> {
-    let mut vec = Vec::new();
+/// Streams the calorie group of every elf out of a reader, one elf at a time.
+struct ElfCalories<R> {
+    lines: io::Lines<R>,
+    line_no: usize,
+    done: bool,
+}
+
+impl<R: BufRead> ElfCalories<R> {
+    fn new(reader: R) -> Self {
+        ElfCalories {
+            lines: reader.lines(),
+            line_no: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ElfCalories<R> {
+    type Item = io::Result<Group>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut group = Group {
+            calories: 0,
+            items: 0,
+        };
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    // The last elf is terminated by the end of the input, a trailing blank
+                    // line has already closed it
+                    self.done = true;
+                    return if group.items > 0 {
+                        Some(Ok(group))
+                    } else {
+                        None
+                    };
+                }
+            };
+            self.line_no += 1;
+
+            if line.is_empty() {
+                return Some(Ok(group));
+            }
+
+            match line.parse::<u64>() {
+                Ok(calories) => match group.calories.checked_add(calories) {
+                    Some(total) => {
+                        group.calories = total;
+                        group.items += 1;
+                    }
+                    None => {
+                        self.done = true;
+                        return Some(Err(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("line {}: elf calorie total overflows", self.line_no),
+                        )));
+                    }
+                },
+                Err(_) => {
+                    self.done = true;
+                    return Some(Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("line {}: invalid calorie count {:?}", self.line_no, line),
+                    )));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Issue {
+    /// A blank line that does not close any food items, e.g. two blank lines in a row.
+    EmptyGroup {
+        line: usize,
+    },
+    InvalidNumber {
+        line: usize,
+        content: String,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::EmptyGroup { line } => write!(f, "line {}: empty elf group", line),
+            Issue::InvalidNumber { line, content } => {
+                write!(f, "line {}: invalid calorie count {:?}", line, content)
+            }
+        }
+    }
+}
 
-    let mut cur_calories = 0;
-    for line_res in read_lines(path)? {
-        let line = &(line_res?);
+/// Strict mode: lists every empty group and non-numeric line instead of stopping at the
+/// first problem.
+fn validate<R: BufRead>(reader: R) -> io::Result<Vec<Issue>> {
+    let mut issues = Vec::new();
+
+    let mut group_items = 0;
+    for (idx, line_res) in reader.lines().enumerate() {
+        let line = line_res?;
 
         if line.is_empty() {
-            vec.push(cur_calories);
-            cur_calories = 0;
+            if group_items == 0 {
+                issues.push(Issue::EmptyGroup { line: idx + 1 });
+            }
+            group_items = 0;
+        } else if line.parse::<u64>().is_ok() {
+            group_items += 1;
         } else {
-            cur_calories += line.parse::<i32>().unwrap();
+            issues.push(Issue::InvalidNumber {
+                line: idx + 1,
+                content: line,
+            });
         }
     }
 
-    vec.push(cur_calories);
+    Ok(issues)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bucket {
+    /// Inclusive lower bound
+    start: u64,
+    /// Exclusive upper bound
+    end: u64,
+    count: usize,
+}
 
-    Ok(vec)
+#[derive(Debug, Clone)]
+struct CalorieStats {
+    sorted_calories: Vec<u64>,
+    empty_groups: usize,
 }
 
-fn main() -> Result<(), Error> {
-    let mut calories = load_calories("input1.txt")?;
-    calories.sort();
+impl CalorieStats {
+    fn from_groups(groups: &[Group]) -> Self {
+        let mut sorted_calories: Vec<u64> = groups.iter().map(|g| g.calories).collect();
+        sorted_calories.sort_unstable();
+
+        CalorieStats {
+            sorted_calories,
+            empty_groups: groups.iter().filter(|g| g.items == 0).count(),
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.sorted_calories.len()
+    }
+
+    fn mean(&self) -> Option<f64> {
+        if self.sorted_calories.is_empty() {
+            return None;
+        }
+        let total: u64 = self.sorted_calories.iter().sum();
+        Some(total as f64 / self.count() as f64)
+    }
+
+    fn median(&self) -> Option<f64> {
+        let n = self.count();
+        if n == 0 {
+            return None;
+        }
+
+        if n % 2 == 1 {
+            Some(self.sorted_calories[n / 2] as f64)
+        } else {
+            Some((self.sorted_calories[n / 2 - 1] + self.sorted_calories[n / 2]) as f64 / 2.0)
+        }
+    }
+
+    /// Nearest-rank percentile, `p` in `0.0..=100.0`.
+    fn percentile(&self, p: f64) -> Option<u64> {
+        let n = self.count();
+        if n == 0 || !(0.0..=100.0).contains(&p) {
+            return None;
+        }
+
+        let rank = ((p / 100.0) * n as f64).ceil() as usize;
+        Some(self.sorted_calories[rank.max(1) - 1])
+    }
+
+    /// Splits `min..=max` into `num_buckets` equally wide buckets.
+    fn histogram(&self, num_buckets: usize) -> Vec<Bucket> {
+        let (min, max) = match (self.sorted_calories.first(), self.sorted_calories.last()) {
+            (Some(min), Some(max)) if num_buckets > 0 => (*min, *max),
+            _ => return Vec::new(),
+        };
+
+        let width = ((max - min) / num_buckets as u64 + 1).max(1);
+        let mut buckets: Vec<Bucket> = (0..num_buckets as u64)
+            .map(|i| Bucket {
+                start: min + i * width,
+                end: min + (i + 1) * width,
+                count: 0,
+            })
+            .collect();
+
+        for calories in &self.sorted_calories {
+            let idx = ((calories - min) / width) as usize;
+            buckets[idx.min(num_buckets - 1)].count += 1;
+        }
+
+        buckets
+    }
+}
+
+impl fmt::Display for CalorieStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "elves:        {}", self.count())?;
+        writeln!(f, "empty groups: {}", self.empty_groups)?;
+        if let (Some(mean), Some(median)) = (self.mean(), self.median()) {
+            writeln!(f, "mean:         {:.2}", mean)?;
+            writeln!(f, "median:       {:.1}", median)?;
+        }
+        for p in [25.0, 75.0, 90.0, 99.0] {
+            if let Some(value) = self.percentile(p) {
+                writeln!(f, "p{:<12}{}", p, value)?;
+            }
+        }
+        let buckets = self.histogram(10);
+        let max_count = buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+        for bucket in buckets {
+            writeln!(
+                f,
+                "{:>8}..{:<8} {:>6} {}",
+                bucket.start,
+                bucket.end,
+                bucket.count,
+                "#".repeat(bucket.count * 50 / max_count)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Elf {
+    /// 1-based position of the elf in the inventory
+    number: usize,
+    calories: u64,
+}
+
+/// Elves carrying more calories are greater. On a tie the elf that comes first in the
+/// inventory is greater, so rankings are deterministic.
+impl Ord for Elf {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.calories
+            .cmp(&other.calories)
+            .then_with(|| other.number.cmp(&self.number))
+    }
+}
+
+impl PartialOrd for Elf {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Returns the `k` elves carrying the most calories, best first, using O(k) memory.
+fn top_k<R: BufRead>(reader: R, k: usize) -> io::Result<Vec<Elf>> {
+    let mut heap: BinaryHeap<Reverse<Elf>> = BinaryHeap::with_capacity(k + 1);
+
+    for (idx, group) in ElfCalories::new(reader).enumerate() {
+        let elf = Elf {
+            number: idx + 1,
+            calories: group?.calories,
+        };
+
+        if heap.len() < k {
+            heap.push(Reverse(elf));
+        } else if let Some(Reverse(min_elf)) = heap.peek() {
+            if elf > *min_elf {
+                heap.pop();
+                heap.push(Reverse(elf));
+            }
+        }
+    }
+
+    Ok(heap.into_sorted_vec().into_iter().map(|e| e.0).collect())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RankedElf {
+    rank: usize,
+    elf: Elf,
+}
+
+/// Assigns competition ranks ("1, 2, 2, 4") to elves sorted best first.
+fn rank(elves: &[Elf]) -> Vec<RankedElf> {
+    let mut ranking: Vec<RankedElf> = Vec::with_capacity(elves.len());
+
+    for (idx, elf) in elves.iter().enumerate() {
+        let rank = match ranking.last() {
+            Some(prev) if prev.elf.calories == elf.calories => prev.rank,
+            _ => idx + 1,
+        };
+        ranking.push(RankedElf { rank, elf: *elf });
+    }
+
+    ranking
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ReportFormat {
+    Table,
+    Csv,
+}
+
+fn write_report<W: Write>(
+    out: &mut W,
+    ranking: &[RankedElf],
+    format: ReportFormat,
+) -> io::Result<()> {
+    match format {
+        ReportFormat::Table => {
+            writeln!(out, "{:>6} {:>8} {:>12}", "rank", "elf", "calories")?;
+            for entry in ranking {
+                writeln!(
+                    out,
+                    "{:>6} {:>8} {:>12}",
+                    entry.rank, entry.elf.number, entry.elf.calories
+                )?;
+            }
+        }
+        ReportFormat::Csv => {
+            writeln!(out, "rank,elf,calories")?;
+            for entry in ranking {
+                writeln!(
+                    out,
+                    "{},{},{}",
+                    entry.rank, entry.elf.number, entry.elf.calories
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_error(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg)
+}
+
+fn main() -> io::Result<()> {
+    // Usage: day01 [path] [--top K] [--report table|csv] [--stats] [--strict]
+    let mut path = String::from("input1.txt");
+    let mut k = 3;
+    let mut report: Option<ReportFormat> = None;
+    let mut print_stats = false;
+    let mut strict = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--top" => {
+                k = args
+                    .next()
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .ok_or_else(|| parse_error("--top expects a number"))?;
+            }
+            "--report" => {
+                report = match args.next().as_deref() {
+                    Some("table") => Some(ReportFormat::Table),
+                    Some("csv") => Some(ReportFormat::Csv),
+                    _ => return Err(parse_error("--report expects table or csv")),
+                };
+            }
+            "--stats" => print_stats = true,
+            "--strict" => strict = true,
+            _ => path = arg,
+        }
+    }
+
+    if strict {
+        let issues = validate(io::BufReader::new(File::open(&path)?))?;
+        for issue in &issues {
+            eprintln!("{}", issue);
+        }
+        if !issues.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} issue(s) found in {}", issues.len(), path),
+            ));
+        }
+    }
+
+    let top = top_k(io::BufReader::new(File::open(&path)?), k.max(3))?;
 
     // Task 1
-    let out_task1 = calories.last().unwrap();
+    let out_task1 = top.first().map_or(0, |e| e.calories);
     println!("Task 1: {}", out_task1);
 
     // Task 2
-    let out_task2: i32 = calories.iter().rev().take(3).sum();
+    let out_task2: u64 = top.iter().take(3).map(|e| e.calories).sum();
     println!("Task 2: {}", out_task2);
 
+    if let Some(format) = report {
+        let ranking = rank(&top[..k.min(top.len())]);
+        write_report(&mut io::stdout().lock(), &ranking, format)?;
+    }
+
+    if print_stats {
+        let groups = ElfCalories::new(io::BufReader::new(File::open(&path)?))
+            .collect::<io::Result<Vec<Group>>>()?;
+        print!("{}", CalorieStats::from_groups(&groups));
+    }
+
     Ok(())
 }
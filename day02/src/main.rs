@@ -1,7 +1,10 @@
-use std::fs::File;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{self, BufRead, Error, ErrorKind};
 use std::path::Path;
-use std::str::FromStr;
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
 where
@@ -11,6 +14,46 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
+fn parse_error(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// The game from the puzzle: A/B/C are the opponent's shapes, X/Y/Z are either our shape
+/// (task 1) or the outcome we need (task 2).
+const CLASSIC_RULES: &str = "\
+shape Rock 1 A X
+shape Paper 2 B Y
+shape Scissors 3 C Z
+beats Rock Scissors
+beats Scissors Paper
+beats Paper Rock
+outcome victory 6 Z
+outcome draw 3 Y
+outcome defeat 0 X
+";
+
+const RPSLS_RULES: &str = "\
+shape Rock 1 A V
+shape Paper 2 B W
+shape Scissors 3 C X
+shape Lizard 4 D Y
+shape Spock 5 E Z
+beats Scissors Paper
+beats Paper Rock
+beats Rock Lizard
+beats Lizard Spock
+beats Spock Scissors
+beats Scissors Lizard
+beats Lizard Paper
+beats Paper Spock
+beats Spock Rock
+beats Rock Scissors
+outcome victory 6 Z
+outcome draw 3 Y
+outcome defeat 0 X
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum GameResult {
     Victory,
     Draw,
@@ -18,151 +61,700 @@ enum GameResult {
 }
 
 impl GameResult {
-    fn score(&self) -> i32 {
-        match self {
-            GameResult::Victory => 6,
-            GameResult::Draw => 3,
-            GameResult::Defeat => 0,
+    fn from_name(name: &str) -> Option<GameResult> {
+        match name {
+            "victory" => Some(GameResult::Victory),
+            "draw" => Some(GameResult::Draw),
+            "defeat" => Some(GameResult::Defeat),
+            _ => None,
         }
     }
 }
 
-impl FromStr for GameResult {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<GameResult, Self::Err> {
-        match s {
-            "X" => Ok(GameResult::Defeat),
-            "Y" => Ok(GameResult::Draw),
-            "Z" => Ok(GameResult::Victory),
-            _ => Err(Error::new(ErrorKind::Other, "Parsing error")),
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::Victory => f.pad("victory"),
+            GameResult::Draw => f.pad("draw"),
+            GameResult::Defeat => f.pad("defeat"),
         }
     }
 }
 
-#[derive(PartialEq, Eq)]
-enum Shape {
-    Rock,
-    Paper,
-    Scissors,
+/// A shape of the game, identified by its index in the `Rules`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Shape(usize);
+
+/// Shapes, beats-relation, scores and input symbols of a Rock-Paper-Scissors-like game.
+///
+/// Config format, one directive per line (`#` starts a comment):
+///
+/// ```text
+/// shape <name> <score> <opponent symbol> <player symbol>
+/// beats <winner> <loser>
+/// outcome <victory|draw|defeat> <score> <symbol>
+/// ```
+#[derive(Debug, Clone)]
+struct Rules {
+    names: Vec<String>,
+    scores: Vec<i32>,
+    /// `beats[a][b]` is true iff shape `a` defeats shape `b`
+    beats: Vec<Vec<bool>>,
+    outcome_scores: HashMap<GameResult, i32>,
+    opponent_symbols: HashMap<String, Shape>,
+    player_symbols: HashMap<String, Shape>,
+    result_symbols: HashMap<String, GameResult>,
 }
 
-impl Shape {
-    fn score(&self) -> i32 {
-        match self {
-            Shape::Rock => 1,
-            Shape::Paper => 2,
-            Shape::Scissors => 3,
-        }
+impl Rules {
+    fn classic() -> Rules {
+        Rules::parse(CLASSIC_RULES).unwrap()
     }
 
-    fn play(&self, other: &Shape) -> GameResult {
-        match self {
-            Shape::Rock => match other {
-                Shape::Rock => GameResult::Draw,
-                Shape::Paper => GameResult::Defeat,
-                Shape::Scissors => GameResult::Victory,
-            },
-            Shape::Paper => match other {
-                Shape::Rock => GameResult::Victory,
-                Shape::Paper => GameResult::Draw,
-                Shape::Scissors => GameResult::Defeat,
-            },
-            Shape::Scissors => match other {
-                Shape::Rock => GameResult::Defeat,
-                Shape::Paper => GameResult::Victory,
-                Shape::Scissors => GameResult::Draw,
-            },
+    fn rpsls() -> Rules {
+        Rules::parse(RPSLS_RULES).unwrap()
+    }
+
+    fn load(name_or_path: &str) -> io::Result<Rules> {
+        match name_or_path {
+            "classic" => Ok(Rules::classic()),
+            "rpsls" => Ok(Rules::rpsls()),
+            path => Rules::parse(&fs::read_to_string(path)?),
         }
     }
-}
 
-// https://www.reddit.com/r/rust/comments/2vqama/parse_string_as_enum_value/
-/// Usage: string.parse::<Shape>()
-impl FromStr for Shape {
-    type Err = Error;
+    fn parse(config: &str) -> io::Result<Rules> {
+        let mut rules = Rules {
+            names: Vec::new(),
+            scores: Vec::new(),
+            beats: Vec::new(),
+            outcome_scores: HashMap::new(),
+            opponent_symbols: HashMap::new(),
+            player_symbols: HashMap::new(),
+            result_symbols: HashMap::new(),
+        };
+        let mut beats: Vec<(String, String)> = Vec::new();
+
+        for (idx, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let err = || parse_error(format!("line {}: invalid directive {:?}", idx + 1, line));
+            match fields[..] {
+                ["shape", name, score, opponent_symbol, player_symbol] => {
+                    let shape = Shape(rules.names.len());
+                    rules.names.push(name.to_string());
+                    rules.scores.push(score.parse().map_err(|_| err())?);
+                    rules
+                        .opponent_symbols
+                        .insert(opponent_symbol.to_string(), shape);
+                    rules
+                        .player_symbols
+                        .insert(player_symbol.to_string(), shape);
+                }
+                ["beats", winner, loser] => beats.push((winner.to_string(), loser.to_string())),
+                ["outcome", result, score, symbol] => {
+                    let result = GameResult::from_name(result).ok_or_else(err)?;
+                    rules
+                        .outcome_scores
+                        .insert(result, score.parse().map_err(|_| err())?);
+                    rules.result_symbols.insert(symbol.to_string(), result);
+                }
+                _ => return Err(err()),
+            }
+        }
 
-    fn from_str(s: &str) -> Result<Shape, Self::Err> {
-        match s {
-            "A" | "X" => Ok(Shape::Rock),
-            "B" | "Y" => Ok(Shape::Paper),
-            "C" | "Z" => Ok(Shape::Scissors),
-            _ => Err(Error::new(ErrorKind::Other, "Parsing error")),
+        let n = rules.names.len();
+        if n == 0 {
+            return Err(parse_error(String::from("no shapes defined")));
+        }
+        rules.beats = vec![vec![false; n]; n];
+        for (winner, loser) in beats {
+            let w = rules.shape_by_name(&winner)?;
+            let l = rules.shape_by_name(&loser)?;
+            if w == l || rules.beats[l.0][w.0] {
+                return Err(parse_error(format!(
+                    "{} and {} cannot beat each other",
+                    winner, loser
+                )));
+            }
+            rules.beats[w.0][l.0] = true;
         }
+
+        for result in [GameResult::Victory, GameResult::Draw, GameResult::Defeat] {
+            if !rules.outcome_scores.contains_key(&result) {
+                return Err(parse_error(format!("missing outcome {:?}", result)));
+            }
+        }
+
+        Ok(rules)
     }
-}
 
-impl GameResult {
-    fn get_other_shape(&self, other_shape: &Shape) -> Shape {
-        match self {
-            GameResult::Defeat => match other_shape {
-                Shape::Paper => Shape::Rock,
-                Shape::Rock => Shape::Scissors,
-                Shape::Scissors => Shape::Paper,
-            },
-            GameResult::Draw => match other_shape {
-                Shape::Paper => Shape::Paper,
-                Shape::Rock => Shape::Rock,
-                Shape::Scissors => Shape::Scissors,
-            },
-            GameResult::Victory => match other_shape {
-                Shape::Paper => Shape::Scissors,
-                Shape::Rock => Shape::Paper,
-                Shape::Scissors => Shape::Rock,
-            },
+    fn shape_by_name(&self, name: &str) -> io::Result<Shape> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .map(Shape)
+            .ok_or_else(|| parse_error(format!("unknown shape {:?}", name)))
+    }
+
+    fn shapes(&self) -> impl Iterator<Item = Shape> {
+        (0..self.names.len()).map(Shape)
+    }
+
+    fn name(&self, shape: Shape) -> &str {
+        &self.names[shape.0]
+    }
+
+    fn score(&self, shape: Shape) -> i32 {
+        self.scores[shape.0]
+    }
+
+    fn outcome_score(&self, result: GameResult) -> i32 {
+        self.outcome_scores[&result]
+    }
+
+    /// Result of `shape` played against `other`, seen from the player of `shape`.
+    fn play(&self, shape: Shape, other: Shape) -> GameResult {
+        if self.beats[shape.0][other.0] {
+            GameResult::Victory
+        } else if self.beats[other.0][shape.0] {
+            GameResult::Defeat
+        } else {
+            GameResult::Draw
         }
     }
+
+    /// The shape that achieves `result` against `other_shape`. If several shapes do, the one
+    /// with the highest score wins.
+    fn get_other_shape(&self, result: GameResult, other_shape: Shape) -> Option<Shape> {
+        self.shapes()
+            .filter(|s| self.play(*s, other_shape) == result)
+            .max_by_key(|s| (self.score(*s), Reverse(s.0)))
+    }
+
+    /// Score of one round in which we play `shape` against `other`.
+    fn round_score(&self, shape: Shape, other: Shape) -> i32 {
+        self.score(shape) + self.outcome_score(self.play(shape, other))
+    }
+
+    /// The shape scoring the most points against `other`.
+    fn best_response(&self, other: Shape) -> Shape {
+        self.shapes()
+            .max_by_key(|s| (self.round_score(*s, other), Reverse(s.0)))
+            .unwrap()
+    }
+
+    fn parse_opponent(&self, symbol: &str) -> io::Result<Shape> {
+        self.opponent_symbols
+            .get(symbol)
+            .copied()
+            .ok_or_else(|| parse_error(format!("unknown opponent symbol {:?}", symbol)))
+    }
+
+    fn parse_player(&self, symbol: &str) -> io::Result<Shape> {
+        self.player_symbols
+            .get(symbol)
+            .copied()
+            .ok_or_else(|| parse_error(format!("unknown player symbol {:?}", symbol)))
+    }
+
+    fn parse_result(&self, symbol: &str) -> io::Result<GameResult> {
+        self.result_symbols
+            .get(symbol)
+            .copied()
+            .ok_or_else(|| parse_error(format!("unknown outcome symbol {:?}", symbol)))
+    }
 }
 
-struct ShapePair(Shape, Shape);
+/// One line of the strategy guide: the opponent's shape and our still uninterpreted symbol.
+struct GuideEntry {
+    opponent: Shape,
+    symbol: String,
+}
 
-fn load_data(path: &str) -> Result<Vec<ShapePair>, Error> {
+fn load_guide(path: &str, rules: &Rules) -> io::Result<Vec<GuideEntry>> {
     let mut vec = Vec::new();
 
     for line in read_lines(path)? {
         let line_str = line?;
-        let pair: Vec<&str> = line_str.split(" ").collect();
-        vec.push(ShapePair(
-            pair[0].parse::<Shape>()?,
-            pair[1].parse::<Shape>()?,
-        ));
+        let (opponent, symbol) = line_str
+            .split_once(' ')
+            .ok_or_else(|| parse_error(format!("invalid round {:?}", line_str)))?;
+        vec.push(GuideEntry {
+            opponent: rules.parse_opponent(opponent)?,
+            symbol: symbol.to_string(),
+        });
     }
 
     Ok(vec)
 }
 
+/// Opponent's shape and our shape.
+struct ShapePair(Shape, Shape);
+
+/// First interpretation: the symbol is the shape we play.
+fn as_shape_pairs(guide: &[GuideEntry], rules: &Rules) -> io::Result<Vec<ShapePair>> {
+    guide
+        .iter()
+        .map(|e| Ok(ShapePair(e.opponent, rules.parse_player(&e.symbol)?)))
+        .collect()
+}
+
 struct ShapeGameResultPair(Shape, GameResult);
 
-fn load_data2(path: &str) -> Result<Vec<ShapeGameResultPair>, Error> {
-    let mut vec = Vec::new();
+/// Second interpretation: the symbol is the outcome we need.
+fn as_result_pairs(guide: &[GuideEntry], rules: &Rules) -> io::Result<Vec<ShapeGameResultPair>> {
+    guide
+        .iter()
+        .map(|e| {
+            Ok(ShapeGameResultPair(
+                e.opponent,
+                rules.parse_result(&e.symbol)?,
+            ))
+        })
+        .collect()
+}
 
-    for line in read_lines(path)? {
-        let line_str = line?;
-        let pair: Vec<&str> = line_str.split(" ").collect();
-        vec.push(ShapeGameResultPair(
-            pair[0].parse::<Shape>()?,
-            pair[1].parse::<GameResult>()?,
-        ));
+#[derive(Debug, Clone, Copy)]
+struct RoundMove {
+    shape: Shape,
+    result: GameResult,
+    score: i32,
+}
+
+impl RoundMove {
+    fn new(rules: &Rules, shape: Shape, opponent: Shape) -> Self {
+        RoundMove {
+            shape,
+            result: rules.play(shape, opponent),
+            score: rules.round_score(shape, opponent),
+        }
     }
+}
 
-    Ok(vec)
+struct RoundAnalysis {
+    opponent: Shape,
+    by_shape: RoundMove,
+    by_result: RoundMove,
+    optimal: RoundMove,
 }
 
-fn main() -> Result<(), Error> {
-    // Task 1:
-    let data = load_data("input.txt")?;
-    let out_task1: i32 = data
+/// Plays every round under both interpretations of the guide and next to the
+/// score-maximising move against the same opponent shape.
+fn analyse(guide: &[GuideEntry], rules: &Rules) -> io::Result<Vec<RoundAnalysis>> {
+    let shape_pairs = as_shape_pairs(guide, rules)?;
+    let result_pairs = as_result_pairs(guide, rules)?;
+
+    shape_pairs
         .iter()
-        .map(|p| p.1.score() + p.1.play(&p.0).score())
-        .sum();
+        .zip(result_pairs.iter())
+        .map(|(p1, p2)| {
+            let opponent = p1.0;
+            let by_result_shape = rules.get_other_shape(p2.1, opponent).ok_or_else(|| {
+                parse_error(format!(
+                    "no shape achieves {} against {}",
+                    p2.1,
+                    rules.name(opponent)
+                ))
+            })?;
+
+            Ok(RoundAnalysis {
+                opponent,
+                by_shape: RoundMove::new(rules, p1.1, opponent),
+                by_result: RoundMove::new(rules, by_result_shape, opponent),
+                optimal: RoundMove::new(rules, rules.best_response(opponent), opponent),
+            })
+        })
+        .collect()
+}
+
+fn print_analysis(analysis: &[RoundAnalysis], rules: &Rules) {
+    println!(
+        "{:>5} {:>9} | {:>9} {:>7} {:>5} {:>6} | {:>9} {:>7} {:>5} {:>6} | {:>6} | {:>9} {:>6}",
+        "round",
+        "opponent",
+        "shape",
+        "outcome",
+        "score",
+        "total",
+        "shape",
+        "outcome",
+        "score",
+        "total",
+        "diff",
+        "optimal",
+        "total"
+    );
+
+    let (mut total1, mut total2, mut total_optimal) = (0, 0, 0);
+    for (idx, round) in analysis.iter().enumerate() {
+        total1 += round.by_shape.score;
+        total2 += round.by_result.score;
+        total_optimal += round.optimal.score;
+
+        println!(
+            "{:>5} {:>9} | {:>9} {:>7} {:>5} {:>6} | {:>9} {:>7} {:>5} {:>6} | {:>6} | {:>9} {:>6}",
+            idx + 1,
+            rules.name(round.opponent),
+            rules.name(round.by_shape.shape),
+            round.by_shape.result,
+            round.by_shape.score,
+            total1,
+            rules.name(round.by_result.shape),
+            round.by_result.result,
+            round.by_result.score,
+            total2,
+            total2 - total1,
+            rules.name(round.optimal.shape),
+            total_optimal
+        );
+    }
+
+    println!(
+        "Gap to optimal: {} (shape interpretation), {} (outcome interpretation)",
+        total_optimal - total1,
+        total_optimal - total2
+    );
+}
+
+/// SplitMix64, small and good enough to make tournaments reproducible from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Picks our next shape given all previous rounds of the match, seen from our side
+/// (`ShapePair(opponent, ours)`).
+trait Strategy {
+    fn name(&self, rules: &Rules) -> String;
+    fn next_shape(&self, history: &[ShapePair], rules: &Rules, rng: &mut Rng) -> Shape;
+}
+
+/// Replays the first interpretation of a strategy guide, wrapping around at its end.
+struct GuideStrategy {
+    name: String,
+    shapes: Vec<Shape>,
+}
+
+impl Strategy for GuideStrategy {
+    fn name(&self, _rules: &Rules) -> String {
+        self.name.clone()
+    }
+
+    fn next_shape(&self, history: &[ShapePair], _rules: &Rules, _rng: &mut Rng) -> Shape {
+        self.shapes[history.len() % self.shapes.len()]
+    }
+}
+
+struct RandomStrategy;
+
+impl Strategy for RandomStrategy {
+    fn name(&self, _rules: &Rules) -> String {
+        String::from("random")
+    }
+
+    fn next_shape(&self, _history: &[ShapePair], rules: &Rules, rng: &mut Rng) -> Shape {
+        Shape(rng.below(rules.names.len()))
+    }
+}
+
+struct ConstantStrategy(Shape);
+
+impl Strategy for ConstantStrategy {
+    fn name(&self, rules: &Rules) -> String {
+        format!("always {}", rules.name(self.0))
+    }
+
+    fn next_shape(&self, _history: &[ShapePair], _rules: &Rules, _rng: &mut Rng) -> Shape {
+        self.0
+    }
+}
+
+/// Plays the best response to the opponent's previous shape.
+struct CounterLastStrategy;
+
+impl Strategy for CounterLastStrategy {
+    fn name(&self, _rules: &Rules) -> String {
+        String::from("counter last")
+    }
+
+    fn next_shape(&self, history: &[ShapePair], rules: &Rules, rng: &mut Rng) -> Shape {
+        match history.last() {
+            Some(last) => rules.best_response(last.0),
+            None => RandomStrategy.next_shape(history, rules, rng),
+        }
+    }
+}
+
+/// Plays the best response to the opponent's most frequent shape so far.
+struct CounterFrequentStrategy;
+
+impl Strategy for CounterFrequentStrategy {
+    fn name(&self, _rules: &Rules) -> String {
+        String::from("counter frequent")
+    }
+
+    fn next_shape(&self, history: &[ShapePair], rules: &Rules, rng: &mut Rng) -> Shape {
+        let mut counts = vec![0; rules.names.len()];
+        history.iter().for_each(|p| counts[p.0 .0] += 1);
+
+        match counts
+            .iter()
+            .enumerate()
+            .max_by_key(|(idx, c)| (**c, Reverse(*idx)))
+        {
+            Some((idx, c)) if *c > 0 => rules.best_response(Shape(idx)),
+            _ => RandomStrategy.next_shape(history, rules, rng),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct EntrantStats {
+    match_scores: Vec<i32>,
+    wins: usize,
+    draws: usize,
+    losses: usize,
+}
+
+impl EntrantStats {
+    fn mean(&self) -> f64 {
+        let total: i32 = self.match_scores.iter().sum();
+        total as f64 / self.match_scores.len().max(1) as f64
+    }
+
+    fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.match_scores
+            .iter()
+            .map(|s| (*s as f64 - mean).powi(2))
+            .sum::<f64>()
+            / self.match_scores.len().max(1) as f64
+    }
+
+    fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.match_scores.len().max(1) as f64
+    }
+}
+
+struct TournamentConfig {
+    seed: u64,
+    rounds: usize,
+    trials: usize,
+}
+
+/// Plays one match and returns the total scores of both entrants.
+fn play_match(
+    a: &dyn Strategy,
+    b: &dyn Strategy,
+    rounds: usize,
+    rules: &Rules,
+    rng: &mut Rng,
+) -> (i32, i32) {
+    let mut history_a: Vec<ShapePair> = Vec::with_capacity(rounds);
+    let mut history_b: Vec<ShapePair> = Vec::with_capacity(rounds);
+    let (mut score_a, mut score_b) = (0, 0);
+
+    for _ in 0..rounds {
+        let shape_a = a.next_shape(&history_a, rules, rng);
+        let shape_b = b.next_shape(&history_b, rules, rng);
+
+        score_a += rules.round_score(shape_a, shape_b);
+        score_b += rules.round_score(shape_b, shape_a);
+        history_a.push(ShapePair(shape_b, shape_a));
+        history_b.push(ShapePair(shape_a, shape_b));
+    }
+
+    (score_a, score_b)
+}
+
+/// Every entrant plays every other entrant once per trial. Each match gets its own RNG
+/// derived from the seed, so results do not depend on the order matches are played in.
+fn run_tournament(
+    entrants: &[Box<dyn Strategy>],
+    rules: &Rules,
+    config: &TournamentConfig,
+) -> Vec<EntrantStats> {
+    let mut stats = vec![EntrantStats::default(); entrants.len()];
+
+    for trial in 0..config.trials {
+        for i in 0..entrants.len() {
+            for j in i + 1..entrants.len() {
+                let match_id = ((trial as u64) << 32) | ((i as u64) << 16) | j as u64;
+                let mut rng = Rng::new(Rng::new(config.seed ^ match_id).next_u64());
+                let (score_i, score_j) = play_match(
+                    entrants[i].as_ref(),
+                    entrants[j].as_ref(),
+                    config.rounds,
+                    rules,
+                    &mut rng,
+                );
+
+                stats[i].match_scores.push(score_i);
+                stats[j].match_scores.push(score_j);
+                match score_i.cmp(&score_j) {
+                    std::cmp::Ordering::Greater => {
+                        stats[i].wins += 1;
+                        stats[j].losses += 1;
+                    }
+                    std::cmp::Ordering::Less => {
+                        stats[i].losses += 1;
+                        stats[j].wins += 1;
+                    }
+                    std::cmp::Ordering::Equal => {
+                        stats[i].draws += 1;
+                        stats[j].draws += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    stats
+}
+
+fn print_tournament(entrants: &[Box<dyn Strategy>], stats: &[EntrantStats], rules: &Rules) {
+    println!(
+        "{:>18} {:>10} {:>12} {:>6} {:>6} {:>6} {:>8}",
+        "strategy", "mean", "variance", "wins", "draws", "losses", "win rate"
+    );
+    for (entrant, s) in entrants.iter().zip(stats.iter()) {
+        println!(
+            "{:>18} {:>10.2} {:>12.2} {:>6} {:>6} {:>6} {:>7.1}%",
+            entrant.name(rules),
+            s.mean(),
+            s.variance(),
+            s.wins,
+            s.draws,
+            s.losses,
+            s.win_rate() * 100.0
+        );
+    }
+}
+
+fn parse_number_arg<T: std::str::FromStr>(value: Option<String>, flag: &str) -> io::Result<T> {
+    value.and_then(|v| v.parse::<T>().ok()).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} expects a number", flag),
+        )
+    })
+}
+
+fn main() -> io::Result<()> {
+    // Usage: day02 [path] [--rules classic|rpsls|<config file>] [--analyse]
+    //              [--tournament] [--seed N] [--rounds N] [--trials N]
+    let mut path = String::from("input.txt");
+    let mut rules = Rules::classic();
+    let mut print_rounds = false;
+    let mut tournament = false;
+    let mut config = TournamentConfig {
+        seed: 2022,
+        rounds: 0,
+        trials: 100,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rules" => {
+                let name = args.next().ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        "--rules expects a preset or a path",
+                    )
+                })?;
+                rules = Rules::load(&name)?;
+            }
+            "--analyse" => print_rounds = true,
+            "--tournament" => tournament = true,
+            "--seed" => config.seed = parse_number_arg(args.next(), "--seed")?,
+            "--rounds" => config.rounds = parse_number_arg(args.next(), "--rounds")?,
+            "--trials" => config.trials = parse_number_arg(args.next(), "--trials")?,
+            _ => path = arg,
+        }
+    }
+
+    let guide = load_guide(&path, &rules)?;
+
+    if print_rounds {
+        print_analysis(&analyse(&guide, &rules)?, &rules);
+        return Ok(());
+    }
+
+    if tournament {
+        let guide_shapes = as_shape_pairs(&guide, &rules)?
+            .iter()
+            .map(|p| p.1)
+            .collect();
+        let mut entrants: Vec<Box<dyn Strategy>> = vec![
+            Box::new(RandomStrategy),
+            Box::new(ConstantStrategy(Shape(0))),
+            Box::new(CounterLastStrategy),
+            Box::new(CounterFrequentStrategy),
+        ];
+        // An empty guide has no shape to repeat, so it cannot take part
+        if !guide.is_empty() {
+            entrants.insert(
+                0,
+                Box::new(GuideStrategy {
+                    name: String::from("strategy guide"),
+                    shapes: guide_shapes,
+                }),
+            );
+        }
+        if config.rounds == 0 {
+            config.rounds = guide.len();
+        }
+        if config.rounds == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the strategy guide is empty, --tournament needs --rounds",
+            ));
+        }
+
+        let stats = run_tournament(&entrants, &rules, &config);
+        print_tournament(&entrants, &stats, &rules);
+        return Ok(());
+    }
+
+    // Task 1:
+    let data = as_shape_pairs(&guide, &rules)?;
+    let out_task1: i32 = data.iter().map(|p| rules.round_score(p.1, p.0)).sum();
     println!("Task 1: {}", out_task1);
 
     // Task 2:
-    let data2 = load_data2("input.txt")?;
-    let out_task2: i32 = data2
-        .iter()
-        .map(|p| p.1.get_other_shape(&p.0).score() + p.1.score())
-        .sum();
+    let data2 = as_result_pairs(&guide, &rules)?;
+    let mut out_task2 = 0;
+    for p in &data2 {
+        let shape = rules.get_other_shape(p.1, p.0).ok_or_else(|| {
+            parse_error(format!(
+                "no shape achieves {} against {}",
+                p.1,
+                rules.name(p.0)
+            ))
+        })?;
+        out_task2 += rules.score(shape) + rules.outcome_score(p.1);
+    }
     println!("Task 2: {}", out_task2);
 
     Ok(())
@@ -1,7 +1,7 @@
-use itertools::Itertools;
-use std::collections::HashSet;
+use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Error, ErrorKind};
 use std::path::Path;
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -12,81 +12,475 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-fn get_score(c: char) -> u32 {
-    c as u32
-        - (if 'a' <= c && c <= 'z' {
-            ('a' as u32) - 1
-        } else {
-            ('A' as u32) - 27
-        })
+/// a-z have priorities 1-26, A-Z have priorities 27-52.
+fn get_score(c: char) -> Option<u32> {
+    match c {
+        'a'..='z' => Some(c as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Some(c as u32 - 'A' as u32 + 27),
+        _ => None,
+    }
 }
 
-fn intersect(sets: Vec<&HashSet<char>>) -> Vec<char> {
-    sets[0]
-        .iter()
-        .map(|c| {
-            for i in 1..sets.len() {
-                if !sets[i].contains(c) {
-                    return None;
-                }
-            }
-            Some(*c)
+fn get_item(priority: u32) -> char {
+    if priority <= 26 {
+        (b'a' + (priority - 1) as u8) as char
+    } else {
+        (b'A' + (priority - 27) as u8) as char
+    }
+}
+
+/// A set of item types, bit `p - 1` is set iff the item with priority `p` is present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    const ALL: ItemSet = ItemSet((1 << 52) - 1);
+
+    fn from_items(items: &str) -> io::Result<ItemSet> {
+        items.chars().try_fold(ItemSet(0), |acc, c| {
+            let score = get_score(c).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, format!("invalid item {:?}", c))
+            })?;
+            Ok(ItemSet(acc.0 | (1 << (score - 1))))
         })
-        .filter(|e| e.is_some())
-        .map(|e| e.unwrap())
-        .collect()
+    }
+
+    fn intersect(self, other: ItemSet) -> ItemSet {
+        ItemSet(self.0 & other.0)
+    }
+
+    fn intersect_all(sets: &[ItemSet]) -> ItemSet {
+        sets.iter().fold(ItemSet::ALL, |acc, s| acc.intersect(*s))
+    }
+
+    fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// Item types in ascending priority order.
+    fn items(self) -> Vec<char> {
+        (1..=52)
+            .filter(|p| self.0 & (1 << (p - 1)) != 0)
+            .map(get_item)
+            .collect()
+    }
+
+    fn priority_sum(self) -> u32 {
+        let mut bits = self.0;
+        let mut sum = 0;
+        while bits != 0 {
+            sum += bits.trailing_zeros() + 1;
+            bits &= bits - 1;
+        }
+        sum
+    }
 }
 
-fn task1(path: &str) -> io::Result<u32> {
-    Ok(read_lines(path)?
-        .map(|line| -> u32 {
-            let rucksack_content = line.unwrap();
+/// Splits a rucksack into `n` equally sized compartments.
+fn split_compartments(rucksack: &str, n: usize) -> io::Result<Vec<ItemSet>> {
+    if !rucksack.is_ascii() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("rucksack {:?} contains non-ASCII items", rucksack),
+        ));
+    }
+    if n == 0 || !rucksack.len().is_multiple_of(n) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "rucksack {:?} cannot be split into {} compartments",
+                rucksack, n
+            ),
+        ));
+    }
+
+    let size = rucksack.len() / n;
+    (0..n)
+        .map(|i| ItemSet::from_items(&rucksack[i * size..(i + 1) * size]))
+        .collect()
+}
 
-            let rucksack_left = &rucksack_content[..rucksack_content.len() / 2];
-            let left_map = rucksack_left.chars().fold(HashSet::new(), |mut acc, c| {
-                acc.insert(c);
-                acc
-            });
+fn task1(path: &str, compartments: usize) -> io::Result<u32> {
+    let mut sum = 0;
+    for line in read_lines(path)? {
+        let compartment_sets = split_compartments(&line?, compartments)?;
+        sum += ItemSet::intersect_all(&compartment_sets).priority_sum();
+    }
+    Ok(sum)
+}
 
-            let rucksack_right = &rucksack_content[rucksack_content.len() / 2..];
-            let right_map = rucksack_right.chars().fold(HashSet::new(), |mut acc, c| {
-                acc.insert(c);
-                acc
-            });
+/// Sums the badges of consecutive groups of `group_size` elves. A trailing incomplete group
+/// is ignored.
+fn task2(path: &str, group_size: usize) -> io::Result<u32> {
+    let rucksacks = read_lines(path)?
+        .map(|l| ItemSet::from_items(&l?))
+        .collect::<io::Result<Vec<ItemSet>>>()?;
 
-            intersect(vec![&left_map, &right_map])
-                .iter()
-                .map(|c| get_score(*c))
-                .sum()
-        })
+    Ok(rucksacks
+        .chunks_exact(group_size.max(1))
+        .map(|group| ItemSet::intersect_all(group).priority_sum())
         .sum())
 }
 
-fn task2(path: &str) -> io::Result<u32> {
-    Ok(read_lines(path)?
-        .map(|l| {
-            l.unwrap().chars().fold(HashSet::new(), |mut acc, c| {
-                acc.insert(c);
-                acc
-            })
-        })
-        .tuples::<(_, _, _)>()
-        .map(|group| -> u32 {
-            intersect(vec![&group.0, &group.1, &group.2])
-                .iter()
-                .map(|c| get_score(*c))
-                .sum()
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Anomaly {
+    EmptyRucksack {
+        line: usize,
+    },
+    UnevenLength {
+        line: usize,
+        len: usize,
+    },
+    InvalidItem {
+        line: usize,
+        column: usize,
+        item: char,
+    },
+    /// The compartments share zero or several item types instead of exactly one.
+    SharedItems {
+        line: usize,
+        items: Vec<char>,
+    },
+    /// The rucksacks of a group share zero or several item types instead of one badge.
+    GroupBadges {
+        first_line: usize,
+        badges: Vec<char>,
+    },
+    IncompleteGroup {
+        first_line: usize,
+        size: usize,
+    },
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Anomaly::EmptyRucksack { line } => write!(f, "line {}: empty rucksack", line),
+            Anomaly::UnevenLength { line, len } => write!(
+                f,
+                "line {}: {} items cannot be split into equal compartments",
+                line, len
+            ),
+            Anomaly::InvalidItem { line, column, item } => {
+                write!(
+                    f,
+                    "line {}, column {}: invalid item {:?}",
+                    line, column, item
+                )
+            }
+            Anomaly::SharedItems { line, items } => write!(
+                f,
+                "line {}: compartments share {} item types {:?}",
+                line,
+                items.len(),
+                items.iter().collect::<String>()
+            ),
+            Anomaly::GroupBadges { first_line, badges } => write!(
+                f,
+                "group starting at line {}: {} badge candidates {:?}",
+                first_line,
+                badges.len(),
+                badges.iter().collect::<String>()
+            ),
+            Anomaly::IncompleteGroup { first_line, size } => write!(
+                f,
+                "group starting at line {}: only {} rucksacks",
+                first_line, size
+            ),
+        }
+    }
+}
+
+/// Like `ItemSet::from_items`, but skips invalid items instead of failing.
+fn valid_items(items: &str) -> ItemSet {
+    ItemSet(
+        items
+            .chars()
+            .filter_map(get_score)
+            .fold(0, |acc, score| acc | (1 << (score - 1))),
+    )
+}
+
+/// Lists every problem of the input instead of silently summing whatever is shared.
+fn validate(path: &str, compartments: usize, group_size: usize) -> io::Result<Vec<Anomaly>> {
+    let mut anomalies = Vec::new();
+    let mut group: Vec<ItemSet> = Vec::with_capacity(group_size);
+    let mut group_start = 1;
+
+    for (idx, line) in read_lines(path)?.enumerate() {
+        let rucksack = line?;
+        let line_no = idx + 1;
+
+        for (col, item) in rucksack.chars().enumerate() {
+            if get_score(item).is_none() {
+                anomalies.push(Anomaly::InvalidItem {
+                    line: line_no,
+                    column: col + 1,
+                    item,
+                });
+            }
+        }
+
+        let len = rucksack.chars().count();
+        if len == 0 {
+            anomalies.push(Anomaly::EmptyRucksack { line: line_no });
+        } else if !len.is_multiple_of(compartments) {
+            anomalies.push(Anomaly::UnevenLength { line: line_no, len });
+        } else {
+            let chars: Vec<char> = rucksack.chars().collect();
+            let shared = ItemSet::intersect_all(
+                &chars
+                    .chunks(len / compartments)
+                    .map(|c| valid_items(&c.iter().collect::<String>()))
+                    .collect::<Vec<ItemSet>>(),
+            );
+            if shared.len() != 1 {
+                anomalies.push(Anomaly::SharedItems {
+                    line: line_no,
+                    items: shared.items(),
+                });
+            }
+        }
+
+        if group.is_empty() {
+            group_start = line_no;
+        }
+        group.push(valid_items(&rucksack));
+        if group.len() == group_size {
+            let badges = ItemSet::intersect_all(&group);
+            if badges.len() != 1 {
+                anomalies.push(Anomaly::GroupBadges {
+                    first_line: group_start,
+                    badges: badges.items(),
+                });
+            }
+            group.clear();
+        }
+    }
+
+    if !group.is_empty() {
+        anomalies.push(Anomaly::IncompleteGroup {
+            first_line: group_start,
+            size: group.len(),
+        });
+    }
+
+    Ok(anomalies)
+}
+
+/// Swaps `count` items of type `left` from the left half with as many items of type `right`
+/// from the right half.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RepairSwap {
+    left: char,
+    right: char,
+    count: usize,
+}
+
+/// Computes the fewest swaps between the two halves of a rucksack after which exactly one
+/// item type is in both halves. Swapping keeps both halves the same length, so the rucksack
+/// still splits in the middle. Returns `None` if the rucksack is malformed or cannot be
+/// repaired by swapping, e.g. `aabb`.
+///
+/// Every other item type has to end up entirely in one half. For each candidate kept type,
+/// a knapsack over the net number of items moved into the left half picks those sides, and
+/// the kept type makes up the difference.
+fn plan_repair(rucksack: &str) -> Option<Vec<RepairSwap>> {
+    if !rucksack.is_ascii() || !rucksack.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let mut left = [0usize; 53];
+    let mut right = [0usize; 53];
+    let half = rucksack.len() / 2;
+    let (left_items, right_items) = rucksack.split_at(half);
+    for c in left_items.chars() {
+        left[get_score(c)? as usize] += 1;
+    }
+    for c in right_items.chars() {
+        right[get_score(c)? as usize] += 1;
+    }
+
+    // Net item counts moved into the left half lie in -half..=half, stored at an offset
+    let width = 2 * half + 1;
+    // Fewest swaps, kept type, its count in the left half afterwards, and whether each other
+    // type ends up in the left half
+    let mut best: Option<(usize, usize, usize, [bool; 53])> = None;
+    for keep in 1..=52 {
+        let total = left[keep] + right[keep];
+        if total < 2 {
+            continue;
+        }
+
+        let others: Vec<usize> = (1..=52)
+            .filter(|p| *p != keep && left[*p] + right[*p] > 0)
+            .collect();
+        // moved[s]: fewest items moved by the types so far for a net of `s - half`
+        let mut moved = vec![None; width];
+        moved[half] = Some(0);
+        let mut choices = Vec::with_capacity(others.len());
+        for p in &others {
+            let mut next: Vec<Option<usize>> = vec![None; width];
+            let mut choice = vec![false; width];
+            for (s, m) in moved.iter().enumerate() {
+                let Some(m) = m else { continue };
+                for (to_left, delta) in [(true, right[*p] as isize), (false, -(left[*p] as isize))]
+                {
+                    let t = s as isize + delta;
+                    if t < 0 || t >= width as isize {
+                        continue;
+                    }
+                    let m = m + delta.unsigned_abs();
+                    if next[t as usize].is_none_or(|n| m < n) {
+                        next[t as usize] = Some(m);
+                        choice[t as usize] = to_left;
+                    }
+                }
+            }
+            moved = next;
+            choices.push(choice);
+        }
+
+        for (s, m) in moved.iter().enumerate() {
+            let Some(m) = m else { continue };
+            // The kept type balances what the others move into the left half
+            let kept_left = left[keep] as isize - (s as isize - half as isize);
+            if kept_left < 1 || kept_left >= total as isize {
+                continue;
+            }
+            let swaps = (m + (kept_left - left[keep] as isize).unsigned_abs()) / 2;
+            if best.is_some_and(|b| b.0 <= swaps) {
+                continue;
+            }
+
+            let mut sides = [false; 53];
+            let mut t = s;
+            for (p, choice) in others.iter().zip(&choices).rev() {
+                sides[*p] = choice[t];
+                let delta = if choice[t] {
+                    right[*p] as isize
+                } else {
+                    -(left[*p] as isize)
+                };
+                t = (t as isize - delta) as usize;
+            }
+            best = Some((swaps, keep, kept_left as usize, sides));
+        }
+    }
+
+    let (_, keep, kept_left, sides) = best?;
+    let mut out_of_left = Vec::new();
+    let mut into_left = Vec::new();
+    for p in 1..=52 {
+        let target = match p {
+            _ if p == keep => kept_left,
+            _ if sides[p] => left[p] + right[p],
+            _ => 0,
+        };
+        let item = get_item(p as u32);
+        if target < left[p] {
+            out_of_left.extend(std::iter::repeat_n(item, left[p] - target));
+        } else {
+            into_left.extend(std::iter::repeat_n(item, target - left[p]));
+        }
+    }
+
+    // Both halves keep their length, so as many items leave the left half as enter it
+    let mut swaps: Vec<RepairSwap> = Vec::new();
+    for (l, r) in out_of_left.into_iter().zip(into_left) {
+        match swaps.last_mut() {
+            Some(swap) if swap.left == l && swap.right == r => swap.count += 1,
+            _ => swaps.push(RepairSwap {
+                left: l,
+                right: r,
+                count: 1,
+            }),
+        }
+    }
+
+    Some(swaps)
+}
+
+fn print_repair_plan(path: &str) -> io::Result<()> {
+    let mut total_swaps = 0;
+    for (idx, line) in read_lines(path)?.enumerate() {
+        let rucksack = line?;
+        match plan_repair(&rucksack) {
+            None => println!("line {}: cannot be repaired", idx + 1),
+            Some(swaps) => {
+                for swap in swaps {
+                    println!(
+                        "line {}: swap {} x {:?} from the left half with {:?} from the right half",
+                        idx + 1,
+                        swap.count,
+                        swap.left,
+                        swap.right
+                    );
+                    total_swaps += swap.count;
+                }
+            }
+        }
+    }
+    println!("Total swaps: {}", total_swaps);
+
+    Ok(())
+}
+
+fn parse_number_arg(value: Option<String>, flag: &str) -> io::Result<usize> {
+    value
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|v| *v > 0)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} expects a positive number", flag),
+            )
         })
-        .sum())
 }
 
 fn main() -> io::Result<()> {
+    // Usage: day03 [path] [--compartments N] [--group-size N] [--validate] [--repair]
+    let mut path = String::from("input.txt");
+    let mut compartments = 2;
+    let mut group_size = 3;
+    let mut check = false;
+    let mut repair = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--compartments" => compartments = parse_number_arg(args.next(), "--compartments")?,
+            "--group-size" => group_size = parse_number_arg(args.next(), "--group-size")?,
+            "--validate" => check = true,
+            "--repair" => repair = true,
+            _ => path = arg,
+        }
+    }
+
+    if check {
+        for anomaly in validate(&path, compartments, group_size)? {
+            println!("{}", anomaly);
+        }
+        return Ok(());
+    }
+
+    if repair {
+        // Repair plans only know how to swap items between two halves
+        if compartments != 2 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "--repair only supports two compartments",
+            ));
+        }
+        return print_repair_plan(&path);
+    }
+
     // Task 1
-    let out_task1: u32 = task1("input.txt")?;
+    let out_task1: u32 = task1(&path, compartments)?;
     println!("Task 1: {}", out_task1);
 
     // Task 2
-    let out_task2: u32 = task2("input.txt")?;
+    let out_task2: u32 = task2(&path, group_size)?;
     println!("Task 2: {}", out_task2);
 
     Ok(())
@@ -0,0 +1,187 @@
+//! Closed integer intervals and normalised unions of them.
+//!
+//! Shared with day15 via `#[path]`, which only needs part of the API.
+#![allow(dead_code)]
+
+use std::cmp;
+
+/// All integers `start..=end`. Never empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Interval {
+    pub start: i32,
+    pub end: i32,
+}
+
+impl Interval {
+    /// Returns `None` if `start > end`.
+    pub fn new(start: i32, end: i32) -> Option<Interval> {
+        if start <= end {
+            Some(Interval { start, end })
+        } else {
+            None
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        (self.end as i64 - self.start as i64 + 1) as u64
+    }
+
+    pub fn contains_point(&self, x: i32) -> bool {
+        self.start <= x && x <= self.end
+    }
+
+    /// True iff `other` lies completely within `self`.
+    pub fn contains(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// True iff the intervals overlap or `other` starts right after `self` ends, or vice
+    /// versa, so that their union is a single interval.
+    pub fn touches(&self, other: &Interval) -> bool {
+        self.start as i64 <= other.end as i64 + 1 && other.start as i64 <= self.end as i64 + 1
+    }
+
+    pub fn intersection(&self, other: &Interval) -> Option<Interval> {
+        Interval::new(
+            cmp::max(self.start, other.start),
+            cmp::min(self.end, other.end),
+        )
+    }
+
+    pub fn union(&self, other: &Interval) -> IntervalSet {
+        IntervalSet::from_iter([*self, *other])
+    }
+
+    /// All points of `self` that are not in `other`: zero, one or two intervals.
+    pub fn difference(&self, other: &Interval) -> IntervalSet {
+        if !self.overlaps(other) {
+            return IntervalSet::from_iter([*self]);
+        }
+
+        let mut out = IntervalSet::new();
+        if self.start < other.start {
+            out.insert(Interval {
+                start: self.start,
+                end: other.start - 1,
+            });
+        }
+        if other.end < self.end {
+            out.insert(Interval {
+                start: other.end + 1,
+                end: self.end,
+            });
+        }
+        out
+    }
+}
+
+/// A union of intervals, kept as sorted, non-overlapping and non-adjacent intervals.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    intervals: Vec<Interval>,
+}
+
+impl IntervalSet {
+    pub fn new() -> IntervalSet {
+        IntervalSet {
+            intervals: Vec::new(),
+        }
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Number of points covered.
+    pub fn len(&self) -> u64 {
+        self.intervals.iter().map(|i| i.len()).sum()
+    }
+
+    pub fn contains_point(&self, x: i32) -> bool {
+        let idx = self.intervals.partition_point(|i| i.end < x);
+        idx < self.intervals.len() && self.intervals[idx].contains_point(x)
+    }
+
+    pub fn insert(&mut self, interval: Interval) {
+        // Intervals in [lo, hi) touch the new one and get merged into it
+        let lo = self
+            .intervals
+            .partition_point(|i| (i.end as i64) + 1 < interval.start as i64);
+        let hi = self
+            .intervals
+            .partition_point(|i| (i.start as i64) <= interval.end as i64 + 1);
+
+        let mut merged = interval;
+        if lo < hi {
+            merged.start = cmp::min(merged.start, self.intervals[lo].start);
+            merged.end = cmp::max(merged.end, self.intervals[hi - 1].end);
+        }
+        self.intervals.splice(lo..hi, [merged]);
+    }
+
+    pub fn union(&self, other: &IntervalSet) -> IntervalSet {
+        let mut out = self.clone();
+        other.intervals.iter().for_each(|i| out.insert(*i));
+        out
+    }
+
+    pub fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut out = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (&self.intervals[i], &other.intervals[j]);
+            if let Some(common) = a.intersection(b) {
+                out.push(common);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { intervals: out }
+    }
+
+    pub fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        let mut out = IntervalSet::new();
+        for interval in &self.intervals {
+            let mut remaining = IntervalSet::from_iter([*interval]);
+            for cut in other.intervals.iter().filter(|o| o.overlaps(interval)) {
+                remaining = remaining
+                    .intervals
+                    .iter()
+                    .flat_map(|r| r.difference(cut).intervals)
+                    .collect();
+            }
+            out = out.union(&remaining);
+        }
+        out
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = Interval>>(iter: T) -> Self {
+        let mut intervals: Vec<Interval> = iter.into_iter().collect();
+        intervals.sort();
+
+        let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+        for interval in intervals {
+            match merged.last_mut() {
+                Some(last) if last.touches(&interval) => {
+                    last.end = cmp::max(last.end, interval.end);
+                }
+                _ => merged.push(interval),
+            }
+        }
+
+        IntervalSet { intervals: merged }
+    }
+}
@@ -1,5 +1,12 @@
+mod interval;
+
+use interval::{Interval, IntervalSet};
+use std::cmp;
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Error, ErrorKind};
 use std::path::Path;
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -10,65 +17,207 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-struct Range(i32, i32);
+/// Parses a section assignment such as `2-4`.
+fn parse_assignment(s: &str) -> io::Result<Interval> {
+    let err = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid assignment {:?}", s),
+        )
+    };
+
+    let (start, end) = s.split_once('-').ok_or_else(err)?;
+    Interval::new(
+        start.parse::<i32>().map_err(|_| err())?,
+        end.parse::<i32>().map_err(|_| err())?,
+    )
+    .ok_or_else(err)
+}
 
-struct RangePair(Range, Range);
+struct RangePair(Interval, Interval);
 
 fn load_data(path: &str) -> io::Result<Vec<RangePair>> {
-    Ok(read_lines(path)?
+    read_lines(path)?
         .map(|l| {
-            let line_str = l.unwrap();
-            let left_and_right: Vec<&str> = line_str.split(",").collect();
-
-            let left_range_split: Vec<&str> = left_and_right[0].split("-").collect();
-            let left_range = Range(
-                left_range_split[0].parse::<i32>().unwrap(),
-                left_range_split[1].parse::<i32>().unwrap(),
-            );
-
-            let right_range_split: Vec<&str> = left_and_right[1].split("-").collect();
-            let right_range = Range(
-                right_range_split[0].parse::<i32>().unwrap(),
-                right_range_split[1].parse::<i32>().unwrap(),
-            );
-
-            RangePair(left_range, right_range)
+            let line_str = l?;
+            let (left, right) = line_str.split_once(',').ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid pair {:?}", line_str),
+                )
+            })?;
+
+            Ok(RangePair(parse_assignment(left)?, parse_assignment(right)?))
         })
-        .collect())
+        .collect()
 }
 
-fn task1(data: &Vec<RangePair>) -> u32 {
+fn task1(data: &[RangePair]) -> usize {
     data.iter()
-        .map(|range_pair| {
-            let r1 = &range_pair.0;
-            let r2 = &range_pair.1;
+        .filter(|p| p.0.contains(&p.1) || p.1.contains(&p.0))
+        .count()
+}
+
+fn task2(data: &[RangePair]) -> usize {
+    data.iter().filter(|p| p.0.overlaps(&p.1)).count()
+}
 
-            if (r1.0 <= r2.0 && r2.1 <= r1.1) || (r2.0 <= r1.0 && r1.1 <= r2.1) {
-                1
+/// One elf of the camp: the line of its pair (1-based) and whether it is the first or second
+/// elf on that line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct ElfId {
+    line: usize,
+    second: bool,
+}
+
+impl fmt::Display for ElfId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.line, if self.second { 2 } else { 1 })
+    }
+}
+
+struct CampCoverage {
+    /// Consecutive sections covered by the same number of elves, in ascending order. Sections
+    /// between the first and last assignment that nobody covers have depth 0.
+    segments: Vec<(Interval, usize)>,
+    /// Overlapping assignments of elves from different lines.
+    overlapping_pairs: Vec<(ElfId, ElfId)>,
+}
+
+impl CampCoverage {
+    fn max_depth(&self) -> usize {
+        self.segments.iter().map(|s| s.1).max().unwrap_or(0)
+    }
+
+    fn sections_with_depth(&self, depth: usize) -> IntervalSet {
+        self.segments
+            .iter()
+            .filter(|s| s.1 == depth)
+            .map(|s| s.0)
+            .collect()
+    }
+}
+
+/// Sweeps over all assignment boundaries of the camp in ascending section order.
+fn analyse_camp(data: &[RangePair]) -> CampCoverage {
+    // (section, delta, elf): the elf's assignment starts (+1) or has just ended (-1) at section
+    let mut events: Vec<(i64, i32, ElfId)> = Vec::with_capacity(data.len() * 4);
+    for (idx, pair) in data.iter().enumerate() {
+        for (second, assignment) in [(false, &pair.0), (true, &pair.1)] {
+            let elf = ElfId {
+                line: idx + 1,
+                second,
+            };
+            events.push((assignment.start as i64, 1, elf));
+            events.push((assignment.end as i64 + 1, -1, elf));
+        }
+    }
+    // Ends sort before starts at the same section
+    events.sort();
+
+    let mut segments: Vec<(Interval, usize)> = Vec::new();
+    let mut overlapping_pairs = Vec::new();
+    let mut active: BTreeSet<ElfId> = BTreeSet::new();
+
+    let mut idx = 0;
+    while idx < events.len() {
+        let section = events[idx].0;
+        while idx < events.len() && events[idx].0 == section {
+            let (_, delta, elf) = events[idx];
+            if delta < 0 {
+                active.remove(&elf);
             } else {
-                0
+                for other in active.iter().filter(|o| o.line != elf.line) {
+                    overlapping_pairs.push((cmp::min(*other, elf), cmp::max(*other, elf)));
+                }
+                active.insert(elf);
             }
-        })
-        .sum()
+            idx += 1;
+        }
+
+        if idx < events.len() {
+            segments.push((
+                Interval {
+                    start: section as i32,
+                    end: (events[idx].0 - 1) as i32,
+                },
+                active.len(),
+            ));
+        }
+    }
+
+    overlapping_pairs.sort();
+
+    CampCoverage {
+        segments,
+        overlapping_pairs,
+    }
 }
 
-fn task2(data: &Vec<RangePair>) -> u32 {
-    data.iter()
-        .map(|range_pair| {
-            let r1 = &range_pair.0;
-            let r2 = &range_pair.1;
+fn print_camp_coverage(coverage: &CampCoverage) {
+    println!("Coverage per section:");
+    for (interval, depth) in &coverage.segments {
+        for section in interval.start..=interval.end {
+            println!("{:>6}: {}", section, depth);
+        }
+    }
+
+    let max_depth = coverage.max_depth();
+    println!(
+        "Maximum depth {} at sections {}",
+        max_depth,
+        format_sections(&coverage.sections_with_depth(max_depth))
+    );
+    println!(
+        "Uncovered sections: {}",
+        format_sections(&coverage.sections_with_depth(0))
+    );
+
+    println!(
+        "Overlapping elves from different lines: {}",
+        coverage.overlapping_pairs.len()
+    );
+    for (a, b) in &coverage.overlapping_pairs {
+        println!("{} {}", a, b);
+    }
+}
 
-            if r1.1 < r2.0 || r2.1 < r1.0 {
-                0
+fn format_sections(sections: &IntervalSet) -> String {
+    if sections.is_empty() {
+        return String::from("-");
+    }
+
+    sections
+        .intervals()
+        .iter()
+        .map(|i| {
+            if i.start == i.end {
+                i.start.to_string()
             } else {
-                1
+                format!("{}-{}", i.start, i.end)
             }
         })
-        .sum()
+        .collect::<Vec<String>>()
+        .join(", ")
 }
 
 fn main() -> io::Result<()> {
-    let data = load_data("input.txt")?;
+    // Usage: day04 [path] [--camp]
+    let mut path = String::from("input.txt");
+    let mut camp = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--camp" => camp = true,
+            _ => path = arg,
+        }
+    }
+
+    let data = load_data(&path)?;
+
+    if camp {
+        print_camp_coverage(&analyse_camp(&data));
+        return Ok(());
+    }
 
     // Task 1
     let out_task1 = task1(&data);
@@ -1,6 +1,8 @@
 use regex::Regex;
+use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Error, ErrorKind};
 use std::path::Path;
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -11,7 +13,7 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Crate(char);
 
 #[derive(Debug, Clone, Copy)]
@@ -24,132 +26,817 @@ struct Instruction {
 #[derive(Debug, Clone)]
 struct Pair(Vec<Vec<Crate>>, Vec<Instruction>);
 
-fn load_data(filename: &str) -> io::Result<Pair> {
-    let data: Vec<String> = read_lines(filename)?.map(|l| l.unwrap()).collect();
-
-    // Parse crates
-    let num_of_stacks = (data[0].len() + 3) / 4;
-    let mut crate_stacks: Vec<Vec<Crate>> = Vec::with_capacity(num_of_stacks);
-    for _ in 0..num_of_stacks {
-        crate_stacks.push(Vec::new());
-    }
-
-    let mut start_idx = 0;
-    while !data[start_idx].starts_with(" 1") {
-        start_idx += 1;
-    }
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
 
-    for idx in (0..start_idx).rev() {
-        let row = &data[idx];
-        let mut iter = row.chars();
+/// Parses the crate drawing, whose last line holds the stack labels `1..=n`. A crate belongs
+/// to the stack whose label is written below it, so multi-digit labels and rows with trimmed
+/// trailing whitespace work as well.
+fn parse_drawing(lines: &[String]) -> io::Result<Vec<Vec<Crate>>> {
+    let (label_row, crate_rows) = lines
+        .split_last()
+        .ok_or_else(|| invalid_data(String::from("missing crate drawing")))?;
 
-        let mut stack_idx = 0;
-        loop {
-            // '['
-            if iter.next().is_none() {
-                break;
+    // Character span of every label in the label row
+    let mut label_spans: Vec<(usize, usize)> = Vec::new();
+    let label_chars: Vec<char> = label_row.chars().collect();
+    let mut pos = 0;
+    while pos < label_chars.len() {
+        if label_chars[pos].is_ascii_digit() {
+            let start = pos;
+            while pos < label_chars.len() && label_chars[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            let label: String = label_chars[start..pos].iter().collect();
+            if label.parse::<usize>() != Ok(label_spans.len() + 1) {
+                return Err(invalid_data(format!(
+                    "expected stack label {} but found {:?}",
+                    label_spans.len() + 1,
+                    label
+                )));
             }
+            label_spans.push((start, pos - 1));
+        } else if label_chars[pos] == ' ' {
+            pos += 1;
+        } else {
+            return Err(invalid_data(format!("invalid label row {:?}", label_row)));
+        }
+    }
 
-            // Crate or empty
-            let crate_id_opt = iter.next();
-            if crate_id_opt.is_some() {
-                let cid = crate_id_opt.unwrap();
-                if cid != ' ' {
-                    crate_stacks[stack_idx].push(Crate(cid));
+    let mut crate_stacks: Vec<Vec<Crate>> = vec![Vec::new(); label_spans.len()];
+    for (row_idx, row) in crate_rows.iter().enumerate().rev() {
+        let chars: Vec<char> = row.chars().collect();
+        let mut pos = 0;
+        while pos < chars.len() {
+            match chars[pos] {
+                ' ' => pos += 1,
+                '[' if pos + 2 < chars.len() && chars[pos + 2] == ']' => {
+                    let stack_idx = label_spans
+                        .iter()
+                        .position(|(start, end)| *start <= pos + 2 && pos <= *end)
+                        .ok_or_else(|| {
+                            invalid_data(format!(
+                                "line {}: crate at column {} has no stack label below it",
+                                row_idx + 1,
+                                pos + 1
+                            ))
+                        })?;
+                    crate_stacks[stack_idx].push(Crate(chars[pos + 1]));
+                    pos += 3;
+                }
+                _ => {
+                    return Err(invalid_data(format!(
+                        "line {}: unexpected character at column {}",
+                        row_idx + 1,
+                        pos + 1
+                    )))
                 }
             }
-
-            stack_idx += 1;
-
-            // ']'
-            iter.next();
-            // Empty space or line break
-            iter.next();
         }
     }
 
-    // Skip empty line and go to start of instructions
-    start_idx += 2;
+    Ok(crate_stacks)
+}
+
+fn load_data(filename: &str) -> io::Result<Pair> {
+    let data: Vec<String> = read_lines(filename)?.collect::<io::Result<_>>()?;
+
+    // The drawing ends at the empty line or, if that is missing, at the first instruction
+    let drawing_end = data
+        .iter()
+        .position(|l| l.trim().is_empty() || l.starts_with("move"))
+        .unwrap_or(data.len());
+    let crate_stacks = parse_drawing(&data[..drawing_end])?;
 
     // Parse instructions
     let mut instructions: Vec<Instruction> = Vec::new();
     let instruction_regex =
-        Regex::new(r"move (?P<num>\d+) from (?P<from>\d+) to (?P<to>\d+)").unwrap();
-    for idx in start_idx..data.len() {
-        let row = &data[idx];
-        let caps = instruction_regex.captures(row).unwrap();
+        Regex::new(r"^move (?P<num>\d+) from (?P<from>\d+) to (?P<to>\d+)$").unwrap();
+    for (idx, row) in data.iter().enumerate().skip(drawing_end) {
+        let row = row.trim();
+        if row.is_empty() {
+            continue;
+        }
+
+        let err = || invalid_data(format!("line {}: invalid instruction {:?}", idx + 1, row));
+        let caps = instruction_regex.captures(row).ok_or_else(err)?;
+        let stack_idx = |label: &str| match label.parse::<usize>() {
+            Ok(n) if 1 <= n && n <= crate_stacks.len() => Ok(n - 1),
+            _ => Err(err()),
+        };
         instructions.push(Instruction {
-            num: caps["num"].parse::<u32>().unwrap(),
-            from: caps["from"].parse::<usize>().unwrap() - 1,
-            to: caps["to"].parse::<usize>().unwrap() - 1,
+            num: caps["num"].parse::<u32>().map_err(|_| err())?,
+            from: stack_idx(&caps["from"])?,
+            to: stack_idx(&caps["to"])?,
         });
     }
 
     Ok(Pair(crate_stacks, instructions))
 }
 
-fn task1(mut data: Pair) -> String {
-    let mut crate_stacks = &mut data.0;
-    for instruction in data.1 {
-        let mut ops = instruction.num;
-        for _ in 0..ops {
-            match crate_stacks[instruction.from].pop() {
-                None => {}
-                Some(x) => crate_stacks[instruction.to].push(x),
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.num,
+            self.from + 1,
+            self.to + 1
+        )
+    }
+}
+
+/// Renders crate stacks in the puzzle's drawing format, labels included.
+struct Drawing<'a>(&'a [Vec<Crate>]);
+
+impl fmt::Display for Drawing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let height = self.0.iter().map(|s| s.len()).max().unwrap_or(0);
+
+        for level in (0..height).rev() {
+            let row: Vec<String> = self
+                .0
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(c) => format!("[{}]", c.0),
+                    None => String::from("   "),
+                })
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+
+        let labels: Vec<String> = (1..=self.0.len()).map(|l| format!("{:^3}", l)).collect();
+        writeln!(f, "{}", labels.join(" "))
+    }
+}
+
+/// The crane takes the top `count` crates of the source stack and puts them down on the
+/// target stack, flipping them over if `reversed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Lift {
+    count: usize,
+    reversed: bool,
+}
+
+fn lift_once<T>(stacks: &mut [Vec<T>], from: usize, to: usize, lift: &Lift) {
+    let start_idx = stacks[from].len().saturating_sub(lift.count);
+    if from == to {
+        if lift.reversed {
+            stacks[from][start_idx..].reverse();
+        }
+        return;
+    }
+
+    let (src, dst) = if from < to {
+        let (left, right) = stacks.split_at_mut(to);
+        (&mut left[from], &mut right[0])
+    } else {
+        let (left, right) = stacks.split_at_mut(from);
+        (&mut right[0], &mut left[to])
+    };
+    if lift.reversed {
+        dst.extend(src.drain(start_idx..).rev());
+    } else {
+        dst.extend(src.drain(start_idx..));
+    }
+}
+
+/// Performs lifts from `from` to `to`. Generic over the stack contents so that crate ids can be
+/// moved alongside the crates themselves.
+fn perform_lifts<T>(stacks: &mut [Vec<T>], from: usize, to: usize, lifts: &[Lift]) {
+    lifts.iter().for_each(|l| lift_once(stacks, from, to, l));
+}
+
+/// Exactly undoes `perform_lifts(stacks, from, to, lifts)`.
+fn revert_lifts<T>(stacks: &mut [Vec<T>], from: usize, to: usize, lifts: &[Lift]) {
+    lifts
+        .iter()
+        .rev()
+        .for_each(|l| lift_once(stacks, to, from, l));
+}
+
+trait Crane {
+    fn name(&self) -> String;
+
+    /// Splits moving the top `num` crates of a stack into the lifts this crane performs.
+    fn plan(&mut self, num: usize) -> Vec<Lift>;
+
+    fn apply(&mut self, crate_stacks: &mut [Vec<Crate>], instruction: &Instruction) {
+        let lifts = self.plan(instruction.num as usize);
+        perform_lifts(crate_stacks, instruction.from, instruction.to, &lifts);
+    }
+}
+
+/// Moves one crate at a time, which reverses the order of the moved crates.
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        String::from("CrateMover 9000")
+    }
+
+    fn plan(&mut self, num: usize) -> Vec<Lift> {
+        vec![
+            Lift {
+                count: 1,
+                reversed: false
+            };
+            num
+        ]
+    }
+}
+
+/// Moves all crates of an instruction at once, keeping their order.
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        String::from("CrateMover 9001")
+    }
+
+    fn plan(&mut self, num: usize) -> Vec<Lift> {
+        vec![Lift {
+            count: num,
+            reversed: false,
+        }]
+    }
+}
+
+/// Like the CrateMover 9001, but lifts at most `capacity` crates at once.
+struct CapacityLimitedCrane {
+    capacity: usize,
+}
+
+impl Crane for CapacityLimitedCrane {
+    fn name(&self) -> String {
+        format!("capacity-limited crane ({} crates)", self.capacity)
+    }
+
+    fn plan(&mut self, num: usize) -> Vec<Lift> {
+        let mut lifts = Vec::new();
+        let mut remaining = num;
+        while remaining > 0 {
+            let chunk = remaining.min(self.capacity);
+            lifts.push(Lift {
+                count: chunk,
+                reversed: false,
+            });
+            remaining -= chunk;
+        }
+        lifts
+    }
+}
+
+/// Lifts all crates of an instruction at once, but every second lift flips them over.
+struct AlternatingCrane {
+    lifts: usize,
+}
+
+impl Crane for AlternatingCrane {
+    fn name(&self) -> String {
+        String::from("alternating crane")
+    }
+
+    fn plan(&mut self, num: usize) -> Vec<Lift> {
+        let reversed = self.lifts % 2 == 1;
+        self.lifts += 1;
+        vec![Lift {
+            count: num,
+            reversed,
+        }]
+    }
+}
+
+/// Parses `9000`, `9001`, `chunked:K` or `alternating`.
+fn parse_crane(s: &str) -> io::Result<Box<dyn Crane>> {
+    match s {
+        "9000" => Ok(Box::new(CrateMover9000)),
+        "9001" => Ok(Box::new(CrateMover9001)),
+        "alternating" => Ok(Box::new(AlternatingCrane { lifts: 0 })),
+        _ => match s.strip_prefix("chunked:").map(|k| k.parse::<usize>()) {
+            Some(Ok(capacity)) if capacity > 0 => Ok(Box::new(CapacityLimitedCrane { capacity })),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown crane model {:?}", s),
+            )),
+        },
+    }
+}
+
+/// Replays a procedure instruction by instruction, forwards and backwards.
+///
+/// Every crate gets an id, numbering the initial crates stack by stack from the bottom, and
+/// the ids are moved alongside the crates. The lifts planned for each instruction are kept, so
+/// stepping back reverts them exactly and stepping forward again replays them without asking
+/// the crane, whose state is only advanced once per instruction.
+struct Rearrangement {
+    instructions: Vec<Instruction>,
+    crane: Box<dyn Crane>,
+    crate_stacks: Vec<Vec<Crate>>,
+    crate_ids: Vec<Vec<usize>>,
+    /// Label and initial stack and level of every crate id
+    labels: Vec<char>,
+    origins: Vec<(usize, usize)>,
+    /// Lifts and moved crate ids of every instruction planned so far
+    history: Vec<(Vec<Lift>, Vec<usize>)>,
+    /// Number of instructions applied to `crate_stacks`
+    step: usize,
+}
+
+impl Rearrangement {
+    fn new(data: Pair, crane: Box<dyn Crane>) -> Self {
+        let labels = data.0.iter().flatten().map(|c| c.0).collect();
+        let mut origins = Vec::new();
+        let crate_ids = data
+            .0
+            .iter()
+            .enumerate()
+            .map(|(stack_idx, stack)| {
+                (0..stack.len())
+                    .map(|level| {
+                        origins.push((stack_idx, level));
+                        origins.len() - 1
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Rearrangement {
+            instructions: data.1,
+            crane,
+            crate_stacks: data.0,
+            crate_ids,
+            labels,
+            origins,
+            history: Vec::new(),
+            step: 0,
+        }
+    }
+
+    fn step(&self) -> usize {
+        self.step
+    }
+
+    fn crate_stacks(&self) -> &[Vec<Crate>] {
+        &self.crate_stacks
+    }
+
+    /// Applies the next instruction. Returns `false` if all instructions are applied.
+    fn step_forward(&mut self) -> io::Result<bool> {
+        let instruction = match self.instructions.get(self.step) {
+            Some(instruction) => *instruction,
+            None => return Ok(false),
+        };
+
+        if self.step == self.history.len() {
+            let available = self.crate_stacks[instruction.from].len();
+            if instruction.num as usize > available {
+                return Err(invalid_data(format!(
+                    "instruction {}: cannot move {} crates from stack {} holding {}",
+                    self.step + 1,
+                    instruction.num,
+                    instruction.from + 1,
+                    available
+                )));
             }
+
+            let moved =
+                self.crate_ids[instruction.from][available - instruction.num as usize..].to_vec();
+            let lifts = self.crane.plan(instruction.num as usize);
+            self.history.push((lifts, moved));
+        }
+
+        let lifts = &self.history[self.step].0;
+        perform_lifts(
+            &mut self.crate_stacks,
+            instruction.from,
+            instruction.to,
+            lifts,
+        );
+        perform_lifts(&mut self.crate_ids, instruction.from, instruction.to, lifts);
+        self.step += 1;
+
+        Ok(true)
+    }
+
+    /// Undoes the last applied instruction. Returns `false` if none is applied.
+    fn step_backward(&mut self) -> bool {
+        if self.step == 0 {
+            return false;
+        }
+
+        self.step -= 1;
+        let instruction = self.instructions[self.step];
+        let lifts = &self.history[self.step].0;
+        revert_lifts(
+            &mut self.crate_stacks,
+            instruction.from,
+            instruction.to,
+            lifts,
+        );
+        revert_lifts(&mut self.crate_ids, instruction.from, instruction.to, lifts);
+
+        true
+    }
+
+    /// Moves to the state after the first `step` instructions.
+    fn goto(&mut self, step: usize) -> io::Result<()> {
+        let step = step.min(self.instructions.len());
+        while self.step > step {
+            self.step_backward();
+        }
+        while self.step < step {
+            self.step_forward()?;
         }
+        Ok(())
     }
 
-    let mut out = String::new();
-    for i in 0..crate_stacks.len() {
-        match crate_stacks[i].last() {
-            Some(c) => out.push(c.0),
-            None => {}
+    /// Index of the first instruction that moves a crate labelled `label`, together with the
+    /// initial stack and level of that crate. Keeps the current step.
+    fn first_touch(&mut self, label: char) -> io::Result<Option<(usize, (usize, usize))>> {
+        let current = self.step;
+        let mut found = None;
+
+        for idx in 0..self.instructions.len() {
+            if idx == self.history.len() {
+                self.goto(idx)?;
+                self.step_forward()?;
+            }
+
+            let moved = &self.history[idx].1;
+            if let Some(id) = moved.iter().find(|id| self.labels[**id] == label) {
+                found = Some((idx, self.origins[*id]));
+                break;
+            }
         }
+
+        self.goto(current)?;
+        Ok(found)
     }
+}
 
-    out
+/// Final crate labels after running `instructions` with a fresh crane, or `None` if some
+/// instruction is impossible.
+fn simulate(
+    crate_stacks: &[Vec<Crate>],
+    instructions: &[Instruction],
+    make_crane: &dyn Fn() -> Box<dyn Crane>,
+) -> Option<Vec<Vec<Crate>>> {
+    let mut stacks = crate_stacks.to_vec();
+    rearrange(&mut stacks, instructions, make_crane().as_mut()).ok()?;
+    Some(stacks)
 }
 
-fn task2(mut data: Pair) -> String {
-    let mut crate_stacks = &mut data.0;
-    for instruction in data.1 {
-        let mut ops = instruction.num;
+/// Shortens a procedure while keeping the final arrangement it produces with the crane built by
+/// `make_crane`. Candidate rewrites (merging consecutive moves between the same stacks,
+/// dropping round trips and dropping single moves) are only kept if replaying the new list
+/// still ends in the same arrangement, so this works for every crane model. It is a greedy
+/// search and does not guarantee the minimum number of operations.
+fn optimise(
+    crate_stacks: &[Vec<Crate>],
+    instructions: &[Instruction],
+    make_crane: &dyn Fn() -> Box<dyn Crane>,
+) -> io::Result<Vec<Instruction>> {
+    let target = simulate(crate_stacks, instructions, make_crane)
+        .ok_or_else(|| invalid_data(String::from("the procedure contains an impossible move")))?;
+    let is_equivalent = |candidate: &[Instruction]| {
+        simulate(crate_stacks, candidate, make_crane) == Some(target.clone())
+    };
+
+    let mut current = instructions.to_vec();
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        // Merge consecutive moves between the same stacks
+        let mut idx = 0;
+        while idx + 1 < current.len() {
+            let (a, b) = (current[idx], current[idx + 1]);
+            if a.from == b.from && a.to == b.to {
+                let mut candidate = current.clone();
+                candidate[idx].num += b.num;
+                candidate.remove(idx + 1);
+                if is_equivalent(&candidate) {
+                    current = candidate;
+                    changed = true;
+                    continue;
+                }
+            }
+            idx += 1;
+        }
 
-        let start_idx = crate_stacks[instruction.from].len() - (ops as usize);
-        for i in start_idx..crate_stacks[instruction.from].len() {
-            let c = crate_stacks[instruction.from][i].clone();
-            crate_stacks[instruction.to].push(c);
+        // Drop moves that are immediately moved back
+        let mut idx = 0;
+        while idx + 1 < current.len() {
+            let (a, b) = (current[idx], current[idx + 1]);
+            if a.from == b.to && a.to == b.from && a.num == b.num {
+                let mut candidate = current.clone();
+                candidate.drain(idx..idx + 2);
+                if is_equivalent(&candidate) {
+                    current = candidate;
+                    changed = true;
+                    continue;
+                }
+            }
+            idx += 1;
         }
 
-        // Remove the last ops elements in from stack
-        let new_length = crate_stacks[instruction.from]
-            .len()
-            .saturating_sub(ops as usize);
-        crate_stacks[instruction.from].truncate(new_length);
+        // Drop moves that do not matter for the final arrangement
+        let mut idx = 0;
+        while idx < current.len() {
+            let mut candidate = current.clone();
+            candidate.remove(idx);
+            if is_equivalent(&candidate) {
+                current = candidate;
+                changed = true;
+            } else {
+                idx += 1;
+            }
+        }
     }
 
-    let mut out = String::new();
-    for i in 0..crate_stacks.len() {
-        match crate_stacks[i].last() {
-            Some(c) => out.push(c.0),
-            None => {}
+    Ok(current)
+}
+
+fn top_of_stacks(crate_stacks: &[Vec<Crate>]) -> String {
+    crate_stacks
+        .iter()
+        .filter_map(|stack| stack.last())
+        .map(|c| c.0)
+        .collect()
+}
+
+/// Fails on the first instruction that moves more crates than its stack holds.
+fn rearrange(
+    crate_stacks: &mut [Vec<Crate>],
+    instructions: &[Instruction],
+    crane: &mut dyn Crane,
+) -> io::Result<()> {
+    for (idx, instruction) in instructions.iter().enumerate() {
+        let available = crate_stacks[instruction.from].len();
+        if instruction.num as usize > available {
+            return Err(invalid_data(format!(
+                "instruction {}: cannot move {} crates from stack {} holding {}",
+                idx + 1,
+                instruction.num,
+                instruction.from + 1,
+                available
+            )));
         }
+        crane.apply(crate_stacks, instruction);
     }
 
-    out
+    Ok(())
+}
+
+fn task1(mut data: Pair) -> io::Result<String> {
+    rearrange(&mut data.0, &data.1, &mut CrateMover9000)?;
+    Ok(top_of_stacks(&data.0))
+}
+
+fn task2(mut data: Pair) -> io::Result<String> {
+    rearrange(&mut data.0, &data.1, &mut CrateMover9001)?;
+    Ok(top_of_stacks(&data.0))
 }
 
 fn main() -> io::Result<()> {
-    let data = load_data("input.txt")?;
+    // Usage: day05 [path] [--crane 9000|9001|chunked:K|alternating] [--draw]
+    //              [--step N] [--first-touch X] [--optimise]
+    let mut path = String::from("input.txt");
+    let mut crane: Option<Box<dyn Crane>> = None;
+    let mut draw = false;
+    let mut step: Option<usize> = None;
+    let mut first_touch: Option<char> = None;
+    let mut crane_model = String::from("9000");
+    let mut optimise_procedure = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--crane" => {
+                let model = args.next().ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "--crane expects a model")
+                })?;
+                crane = Some(parse_crane(&model)?);
+                crane_model = model;
+            }
+            "--draw" => draw = true,
+            "--optimise" => optimise_procedure = true,
+            "--step" => {
+                step = Some(
+                    args.next()
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .ok_or_else(|| {
+                            Error::new(ErrorKind::InvalidInput, "--step expects a number")
+                        })?,
+                );
+            }
+            "--first-touch" => {
+                first_touch =
+                    Some(args.next().and_then(|v| v.chars().next()).ok_or_else(|| {
+                        Error::new(ErrorKind::InvalidInput, "--first-touch expects a crate")
+                    })?);
+            }
+            _ => path = arg,
+        }
+    }
+
+    let mut data = load_data(&path)?;
+
+    if optimise_procedure {
+        let make_crane = || parse_crane(&crane_model).unwrap();
+        let optimised = optimise(&data.0, &data.1, &make_crane)?;
+        eprintln!(
+            "Reduced {} instructions to {}",
+            data.1.len(),
+            optimised.len()
+        );
+
+        // Same format as the input, so the result can be fed back in
+        println!("{}", Drawing(&data.0));
+        for instruction in optimised {
+            println!("{}", instruction);
+        }
+        return Ok(());
+    }
+
+    if step.is_some() || first_touch.is_some() {
+        let crane = crane.unwrap_or_else(|| Box::new(CrateMover9000));
+        let mut replay = Rearrangement::new(data, crane);
+
+        if let Some(label) = first_touch {
+            match replay.first_touch(label)? {
+                Some((idx, (stack_idx, level))) => println!(
+                    "Crate {} (stack {}, level {}) is first moved by instruction {}: {}",
+                    label,
+                    stack_idx + 1,
+                    level + 1,
+                    idx + 1,
+                    replay.instructions[idx]
+                ),
+                None => println!("Crate {} is never moved", label),
+            }
+        }
+
+        if let Some(step) = step {
+            replay.goto(step)?;
+            println!("After step {}:", replay.step());
+            print!("{}", Drawing(replay.crate_stacks()));
+        }
+
+        return Ok(());
+    }
+
+    if let Some(mut crane) = crane {
+        if draw {
+            print!("{}", Drawing(&data.0));
+        }
+        rearrange(&mut data.0, &data.1, crane.as_mut())?;
+        println!("{}: {}", crane.name(), top_of_stacks(&data.0));
+        if draw {
+            print!("{}", Drawing(&data.0));
+        }
+        return Ok(());
+    }
+
+    if draw {
+        // Same as the tasks below, with the stacks drawn before and after each procedure
+        print!("{}", Drawing(&data.0));
+        let cranes: [Box<dyn Crane>; 2] = [Box::new(CrateMover9000), Box::new(CrateMover9001)];
+        for (idx, mut crane) in cranes.into_iter().enumerate() {
+            let mut crate_stacks = data.0.clone();
+            rearrange(&mut crate_stacks, &data.1, crane.as_mut())?;
+            println!("Task {}: {}", idx + 1, top_of_stacks(&crate_stacks));
+            print!("{}", Drawing(&crate_stacks));
+        }
+        return Ok(());
+    }
 
     // Task 1
-    let out_task1 = task1(data.clone());
+    let out_task1 = task1(data.clone())?;
     println!("Task 1: {}", out_task1);
 
     // Task 2
-    let out_task2 = task2(data);
+    let out_task2 = task2(data)?;
     println!("Task 2: {}", out_task2);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stacks from bottom to top.
+    fn stacks(stacks: &[&str]) -> Vec<Vec<Crate>> {
+        stacks
+            .iter()
+            .map(|s| s.chars().map(Crate).collect())
+            .collect()
+    }
+
+    /// Instructions from `(num, from, to)` with 1-based stack labels.
+    fn procedure(moves: &[(u32, usize, usize)]) -> Vec<Instruction> {
+        moves
+            .iter()
+            .map(|(num, from, to)| Instruction {
+                num: *num,
+                from: from - 1,
+                to: to - 1,
+            })
+            .collect()
+    }
+
+    fn optimise_with(
+        model: &str,
+        crate_stacks: &[Vec<Crate>],
+        instructions: &[Instruction],
+    ) -> Vec<String> {
+        let make_crane = || parse_crane(model).unwrap();
+        optimise(crate_stacks, instructions, &make_crane)
+            .unwrap()
+            .iter()
+            .map(|i| i.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn merges_consecutive_moves_if_the_crane_allows() {
+        let crate_stacks = stacks(&["AB", ""]);
+        let instructions = procedure(&[(1, 1, 2), (1, 1, 2)]);
+        assert_eq!(
+            optimise_with("9000", &crate_stacks, &instructions),
+            vec!["move 2 from 1 to 2"]
+        );
+        // Moving both at once would keep their order on the 9001
+        assert_eq!(
+            optimise_with("9001", &crate_stacks, &instructions),
+            vec!["move 1 from 1 to 2", "move 1 from 1 to 2"]
+        );
+    }
+
+    #[test]
+    fn drops_round_trips() {
+        let crate_stacks = stacks(&["AB", "C", ""]);
+        let instructions = procedure(&[(1, 2, 3), (1, 3, 2), (1, 1, 2)]);
+        assert_eq!(
+            optimise_with("9001", &crate_stacks, &instructions),
+            vec!["move 1 from 1 to 2"]
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_procedures() {
+        let crate_stacks = stacks(&["A", ""]);
+        let make_crane = || parse_crane("9000").unwrap();
+        assert!(optimise(&crate_stacks, &procedure(&[(2, 1, 2)]), &make_crane).is_err());
+    }
+
+    #[test]
+    fn keeps_the_final_arrangement() {
+        let Pair(crate_stacks, instructions) = load_data("example.txt").unwrap();
+        let mut rng = 1u64;
+        let mut random = |n: usize| {
+            rng = rng
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (rng >> 33) as usize % n
+        };
+
+        let mut procedures = vec![instructions];
+        for _ in 0..50 {
+            // Random procedures that only move crates that are there
+            let mut heights: Vec<usize> = crate_stacks.iter().map(|s| s.len()).collect();
+            let mut instructions = Vec::new();
+            for _ in 0..random(8) {
+                let from = random(heights.len());
+                let to = random(heights.len());
+                if heights[from] == 0 || from == to {
+                    continue;
+                }
+                let num = 1 + random(heights[from]);
+                heights[from] -= num;
+                heights[to] += num;
+                instructions.push(Instruction {
+                    num: num as u32,
+                    from,
+                    to,
+                });
+            }
+            procedures.push(instructions);
+        }
+
+        for model in ["9000", "9001", "chunked:2", "alternating"] {
+            let make_crane = || parse_crane(model).unwrap();
+            for instructions in &procedures {
+                let optimised = optimise(&crate_stacks, instructions, &make_crane).unwrap();
+                assert!(optimised.len() <= instructions.len());
+                assert_eq!(
+                    simulate(&crate_stacks, &optimised, &make_crane),
+                    simulate(&crate_stacks, instructions, &make_crane)
+                );
+            }
+        }
+    }
+}
@@ -1,64 +1,338 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
+use std::env;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, Error, ErrorKind, Read, Write};
 
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+/// Reads the whole datastream. Like `--stream`, a single trailing line terminator is not
+/// part of it.
+fn load_data(filename: &str) -> io::Result<Vec<char>> {
+    let data = fs::read_to_string(filename)?;
+    let data = data
+        .strip_suffix("\r\n")
+        .or_else(|| data.strip_suffix('\n'))
+        .or_else(|| data.strip_suffix('\r'))
+        .unwrap_or(&data);
+    Ok(data.chars().collect())
 }
 
-fn load_data(filename: &str) -> io::Result<Vec<char>> {
-    Ok(read_lines(filename)?.next().unwrap()?.chars().collect())
+/// Yields every position (number of symbols processed) at which the last `length` symbols
+/// are pairwise distinct. Works for bytes and chars alike.
+struct DistinctWindows<'a, T> {
+    data: &'a [T],
+    length: usize,
+    window: HashMap<&'a T, usize>,
+    pos: usize,
+}
+
+impl<'a, T: Eq + Hash> DistinctWindows<'a, T> {
+    fn new(data: &'a [T], length: usize) -> Self {
+        DistinctWindows {
+            data,
+            length,
+            window: HashMap::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<T: Eq + Hash> Iterator for DistinctWindows<'_, T> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.length == 0 {
+            return None;
+        }
+
+        while self.pos < self.data.len() {
+            if self.pos >= self.length {
+                let old = &self.data[self.pos - self.length];
+                let value = self.window.get_mut(old).unwrap();
+                *value -= 1;
+                if *value == 0 {
+                    self.window.remove(old);
+                }
+            }
+
+            *self.window.entry(&self.data[self.pos]).or_insert(0) += 1;
+            self.pos += 1;
+
+            if self.window.len() == self.length {
+                return Some(self.pos);
+            }
+        }
+
+        None
+    }
+}
+
+/// Position after the first `message_length` distinct symbols, `None` if there is no marker.
+fn detect_start<T: Eq + Hash>(data: &[T], message_length: usize) -> Option<usize> {
+    DistinctWindows::new(data, message_length).next()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WindowStats {
+    first: Option<usize>,
+    last: Option<usize>,
+    count: usize,
+}
+
+fn window_stats<T: Eq + Hash>(data: &[T], length: usize) -> WindowStats {
+    let mut stats = WindowStats {
+        first: None,
+        last: None,
+        count: 0,
+    };
+    for pos in DistinctWindows::new(data, length) {
+        stats.first = stats.first.or(Some(pos));
+        stats.last = Some(pos);
+        stats.count += 1;
+    }
+
+    stats
+}
+
+/// Start index and length of the longest run of pairwise distinct symbols.
+fn longest_distinct_run<T: Eq + Hash>(data: &[T]) -> (usize, usize) {
+    let mut longest_run = (0, 0);
+    let mut last_seen: HashMap<&T, usize> = HashMap::new();
+    let mut run_start = 0;
+    for (idx, symbol) in data.iter().enumerate() {
+        if let Some(prev) = last_seen.insert(symbol, idx) {
+            run_start = run_start.max(prev + 1);
+        }
+        if idx + 1 - run_start > longest_run.1 {
+            longest_run = (run_start, idx + 1 - run_start);
+        }
+    }
+
+    longest_run
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Marker {
+    length: usize,
+    /// Number of bytes processed when the marker is complete
+    position: u64,
+}
+
+/// Finds markers of several lengths at once in a stream, one byte at a time, with constant
+/// memory. Every byte counts towards positions, except for a line terminator at the very end
+/// of the stream: line break bytes are held back until the next byte shows that they are data.
+struct MarkerDetector {
+    lengths: Vec<usize>,
+    /// Occurrences of every byte value in the window of each marker length
+    counts: Vec<[u16; 256]>,
+    /// Number of distinct byte values in the window of each marker length
+    distinct: Vec<usize>,
+    /// The last `max(lengths)` bytes
+    ring: Vec<u8>,
+    position: u64,
+    /// Trailing `\r`, `\n` or `\r\n` that has not been fed yet
+    held: Vec<u8>,
 }
 
-fn detect_start(data: &Vec<char>, message_length: usize) -> usize {
-    let mut window = HashMap::new();
-    for i in 0..message_length {
-        window.entry(&data[i]).and_modify(|v| *v += 1).or_insert(1);
+impl MarkerDetector {
+    fn new(lengths: &[usize]) -> Self {
+        let max_length = lengths.iter().copied().max().unwrap_or(0);
+        assert!(
+            max_length <= u16::MAX as usize,
+            "marker lengths must fit the byte counters"
+        );
+
+        MarkerDetector {
+            lengths: lengths.to_vec(),
+            counts: vec![[0; 256]; lengths.len()],
+            distinct: vec![0; lengths.len()],
+            ring: vec![0; max_length.max(1)],
+            position: 0,
+            held: Vec::with_capacity(2),
+        }
     }
-    if window.len() == message_length {
-        return message_length as usize;
+
+    /// Pushes one byte and calls `on_marker` for every marker that ends at it. Bytes that may
+    /// form the final line terminator are only fed once a later byte arrives.
+    fn push<F: FnMut(Marker)>(&mut self, byte: u8, mut on_marker: F) {
+        let line_break = byte == b'\n' || byte == b'\r';
+        if line_break && self.held == b"\r" && byte == b'\n' {
+            self.held.push(byte);
+            return;
+        }
+
+        let mut held = std::mem::take(&mut self.held);
+        for held_byte in held.drain(..) {
+            self.feed(held_byte, &mut on_marker);
+        }
+        self.held = held;
+
+        if line_break {
+            self.held.push(byte);
+        } else {
+            self.feed(byte, &mut on_marker);
+        }
     }
 
-    for i in message_length..data.len() {
-        let value = window.get_mut(&data[i - message_length]).unwrap();
-        *value -= 1;
-        if *value == 0 {
-            window.remove(&data[i - message_length]);
+    fn feed<F: FnMut(Marker)>(&mut self, byte: u8, on_marker: &mut F) {
+        let ring_len = self.ring.len() as u64;
+        for (idx, length) in self.lengths.iter().enumerate() {
+            let counts = &mut self.counts[idx];
+
+            if self.position >= *length as u64 {
+                let old = self.ring[((self.position - *length as u64) % ring_len) as usize];
+                counts[old as usize] -= 1;
+                if counts[old as usize] == 0 {
+                    self.distinct[idx] -= 1;
+                }
+            }
+
+            if counts[byte as usize] == 0 {
+                self.distinct[idx] += 1;
+            }
+            counts[byte as usize] += 1;
+
+            if self.distinct[idx] == *length {
+                on_marker(Marker {
+                    length: *length,
+                    position: self.position + 1,
+                });
+            }
         }
 
-        window.entry(&data[i]).and_modify(|v| *v += 1).or_insert(1);
+        self.ring[(self.position % ring_len) as usize] = byte;
+        self.position += 1;
+    }
+}
+
+/// Runs the detector over a whole stream. Stops early once `on_marker` returns `false`.
+fn scan<R: Read, F: FnMut(Marker) -> bool>(
+    mut reader: R,
+    lengths: &[usize],
+    mut on_marker: F,
+) -> io::Result<()> {
+    let mut detector = MarkerDetector::new(lengths);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut keep_going = true;
 
-        if window.len() == message_length {
-            return i + 1;
+    while keep_going {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for byte in &buffer[..n] {
+            detector.push(*byte, |marker| keep_going &= on_marker(marker));
+            if !keep_going {
+                break;
+            }
         }
     }
 
-    0
+    Ok(())
 }
 
-fn task1(data: &Vec<char>) -> usize {
+/// First marker of every length, in the order of `lengths`.
+fn first_markers<R: Read>(reader: R, lengths: &[usize]) -> io::Result<Vec<Option<u64>>> {
+    let mut first = vec![None; lengths.len()];
+    scan(reader, lengths, |marker| {
+        for (idx, length) in lengths.iter().enumerate() {
+            if *length == marker.length && first[idx].is_none() {
+                first[idx] = Some(marker.position);
+            }
+        }
+        first.iter().any(|p| p.is_none())
+    })?;
+    Ok(first)
+}
+
+fn task1(data: &[char]) -> Option<usize> {
     detect_start(data, 4)
 }
 
-fn task2(data: &Vec<char>) -> usize {
+fn task2(data: &[char]) -> Option<usize> {
     detect_start(data, 14)
 }
 
 fn main() -> io::Result<()> {
-    let data = load_data("input.txt")?;
+    // Usage: day06 [path] [--stream] [--all] [--stats] [--lengths 4,14]
+    let mut path = String::from("input.txt");
+    let mut stats = false;
+    let mut stream = false;
+    let mut all = false;
+    let mut lengths = vec![4, 14];
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stream" => stream = true,
+            "--all" => all = true,
+            "--stats" => stats = true,
+            "--lengths" => {
+                lengths = args
+                    .next()
+                    .and_then(|v| v.split(',').map(|l| l.parse::<usize>().ok()).collect())
+                    .filter(|l: &Vec<usize>| l.iter().all(|n| 0 < *n && *n <= 256))
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            "--lengths expects comma separated numbers between 1 and 256",
+                        )
+                    })?;
+            }
+            _ => path = arg,
+        }
+    }
+
+    if stream || all {
+        let reader = io::BufReader::new(File::open(&path)?);
+        if all {
+            let mut out = io::BufWriter::new(io::stdout().lock());
+            let mut result = Ok(());
+            scan(reader, &lengths, |marker| {
+                result = writeln!(out, "{} {}", marker.length, marker.position);
+                result.is_ok()
+            })?;
+            return result;
+        }
+
+        for (length, position) in lengths.iter().zip(first_markers(reader, &lengths)?) {
+            match position {
+                Some(p) => println!("Marker of length {}: {}", length, p),
+                None => println!("Marker of length {}: none", length),
+            }
+        }
+        return Ok(());
+    }
+
+    let data = load_data(&path)?;
+
+    if stats {
+        for length in &lengths {
+            let s = window_stats(&data, *length);
+            println!(
+                "Length {}: {} markers, first {:?}, last {:?}",
+                length, s.count, s.first, s.last
+            );
+        }
+        let (start, len) = longest_distinct_run(&data);
+        println!(
+            "Longest distinct run: {} symbols starting at {}",
+            len, start
+        );
+        return Ok(());
+    }
+
+    let format = |out: Option<usize>| out.map_or(String::from("none"), |p| p.to_string());
 
     // Task 1
     let out_task1 = task1(&data);
-    println!("Task 1: {}", out_task1);
+    println!("Task 1: {}", format(out_task1));
 
     // Task 2
     let out_task2 = task2(&data);
-    println!("Task 2: {}", out_task2);
+    println!("Task 2: {}", format(out_task2));
 
     Ok(())
 }
@@ -1,6 +1,7 @@
-use regex::Regex;
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Error, ErrorKind, Write};
 use std::path::Path;
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -12,88 +13,1107 @@ where
 }
 
 fn load_data(filename: &str) -> io::Result<Vec<String>> {
-    Ok(read_lines(filename)?.map(|l| l.unwrap()).collect())
-}
-
-/**
- * Instructions:
- *
- * cd <dir> ==> add 0 to stack
- * cd .. ==> pop from stack and add to previous
- * ls ==> update last element in stack
- */
-
-fn collect_dir_sizes(data: &Vec<String>) -> Vec<u64> {
-    let mut dir_sizes = Vec::new();
-
-    let filesize_regex = Regex::new(r"(?P<filesize>\d+) *").unwrap();
-
-    let mut path = Vec::new();
-    let mut line_idx = 0;
-    while line_idx < data.len() {
-        let line = &data[line_idx];
-        if line.starts_with("$ cd ..") {
-            // Move one directory level up
-            let child_size = path.pop().unwrap();
-            dir_sizes.push(child_size);
-            *path.last_mut().unwrap() += child_size;
-            line_idx += 1;
-        } else if line.starts_with("$ ls") {
-            // Update directory size
-            line_idx += 1;
-            // Iterate as long as we have ls output
-            while line_idx < data.len() && !data[line_idx].starts_with("$") {
-                if !data[line_idx].starts_with("dir") {
-                    // We have a line with filesize!
-                    let caps = filesize_regex.captures(&data[line_idx]).unwrap();
-                    *path.last_mut().unwrap() += caps["filesize"].parse::<u64>().unwrap();
+    read_lines(filename)?.collect()
+}
+
+type NodeId = usize;
+
+#[derive(Debug)]
+enum NodeKind {
+    Dir { children: BTreeMap<String, NodeId> },
+    File { size: u64 },
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    parent: Option<NodeId>,
+    kind: NodeKind,
+}
+
+/// The filesystem explored by a terminal transcript. Nodes live in an arena and every node is
+/// created after its parent, so children always have larger ids.
+#[derive(Debug)]
+struct FsTree {
+    nodes: Vec<Node>,
+}
+
+impl FsTree {
+    const ROOT: NodeId = 0;
+
+    fn new() -> Self {
+        FsTree {
+            nodes: vec![Node {
+                name: String::from("/"),
+                parent: None,
+                kind: NodeKind::Dir {
+                    children: BTreeMap::new(),
+                },
+            }],
+        }
+    }
+
+    /// Replays `$ cd` and `$ ls` commands.
+    ///
+    /// `cd /` jumps to the root from anywhere, listing a directory again only adds what is
+    /// new, and changing into a directory that has not been listed is an error.
+    fn parse(data: &[String]) -> io::Result<FsTree> {
+        let mut tree = FsTree::new();
+        let mut cwd = FsTree::ROOT;
+        let mut listing = false;
+
+        for (idx, line) in data.iter().enumerate() {
+            let err = |msg: &str| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "line {} in {}: {} ({:?})",
+                        idx + 1,
+                        tree.path(cwd),
+                        msg,
+                        line
+                    ),
+                )
+            };
+
+            if let Some(target) = line.strip_prefix("$ cd ") {
+                listing = false;
+                cwd = match target {
+                    "/" => FsTree::ROOT,
+                    ".." => tree.nodes[cwd]
+                        .parent
+                        .ok_or_else(|| err("root has no parent"))?,
+                    name => match tree.child(cwd, name) {
+                        Some(id) if tree.is_dir(id) => id,
+                        Some(_) => return Err(err("not a directory")),
+                        None => return Err(err("unknown directory")),
+                    },
+                };
+            } else if line == "$ ls" {
+                listing = true;
+            } else if !listing {
+                return Err(err("unexpected line"));
+            } else if let Some(name) = line.strip_prefix("dir ") {
+                match tree.child(cwd, name) {
+                    Some(id) if tree.is_dir(id) => {}
+                    Some(_) => return Err(err("listed as file before")),
+                    None => {
+                        tree.add(
+                            cwd,
+                            name,
+                            NodeKind::Dir {
+                                children: BTreeMap::new(),
+                            },
+                        );
+                    }
+                }
+            } else {
+                let (size, name) = line
+                    .split_once(' ')
+                    .and_then(|(size, name)| Some((size.parse::<u64>().ok()?, name)))
+                    .ok_or_else(|| err("invalid listing entry"))?;
+                match tree.child(cwd, name) {
+                    Some(id) => match tree.nodes[id].kind {
+                        NodeKind::File { size: old } if old == size => {}
+                        _ => return Err(err("conflicts with earlier listing")),
+                    },
+                    None => {
+                        tree.add(cwd, name, NodeKind::File { size });
+                    }
                 }
-                line_idx += 1;
             }
+        }
+
+        Ok(tree)
+    }
+
+    fn add(&mut self, parent: NodeId, name: &str, kind: NodeKind) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(parent),
+            kind,
+        });
+        if let NodeKind::Dir { children } = &mut self.nodes[parent].kind {
+            children.insert(name.to_string(), id);
+        }
+        id
+    }
+
+    fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[dir].kind {
+            NodeKind::Dir { children } => children.get(name).copied(),
+            NodeKind::File { .. } => None,
+        }
+    }
+
+    fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].kind, NodeKind::Dir { .. })
+    }
+
+    /// Total size of every node, files included, indexed by node id.
+    fn sizes(&self) -> Vec<u64> {
+        let mut sizes: Vec<u64> = self
+            .nodes
+            .iter()
+            .map(|n| match n.kind {
+                NodeKind::File { size } => size,
+                NodeKind::Dir { .. } => 0,
+            })
+            .collect();
+
+        // Children come after their parents, so walking backwards finishes every subtree
+        // before it is added to its parent
+        for id in (1..self.nodes.len()).rev() {
+            let parent = self.nodes[id].parent.unwrap();
+            sizes[parent] += sizes[id];
+        }
+
+        sizes
+    }
+
+    /// Sizes of all directories, the root included.
+    fn dir_sizes(&self) -> Vec<u64> {
+        self.sizes()
+            .into_iter()
+            .enumerate()
+            .filter(|(id, _)| self.is_dir(*id))
+            .map(|(_, size)| size)
+            .collect()
+    }
+
+    fn path(&self, id: NodeId) -> String {
+        if id == FsTree::ROOT {
+            return String::from("/");
+        }
+
+        let mut names = Vec::new();
+        let mut cur = Some(id);
+        while let Some(node) = cur.filter(|n| *n != FsTree::ROOT) {
+            names.push(self.nodes[node].name.as_str());
+            cur = self.nodes[node].parent;
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+}
+
+/// Matches `name` against a shell-style pattern with `*` (any run of characters) and `?`
+/// (any single character).
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Greedy matching with backtracking to the most recent `*`
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
         } else {
-            // Must be "$ cd <dir>"
-            path.push(0);
-            line_idx += 1;
+            return false;
         }
     }
 
-    // Clean up stack
-    while !path.is_empty() {
-        let child_size = path.pop().unwrap();
-        dir_sizes.push(child_size);
-        if !path.is_empty() {
-            *path.last_mut().unwrap() += child_size;
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Formats a size like `du -h`, with 1024-based units.
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+
+    if size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if value < 10.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeType {
+    File,
+    Dir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    Size,
+    Path,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FindQuery {
+    name: Option<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    node_type: Option<NodeType>,
+}
+
+impl FsTree {
+    /// Number of directories between the root and `id`; the root has depth 0.
+    fn depth(&self, id: NodeId) -> usize {
+        let mut depth = 0;
+        let mut cur = self.nodes[id].parent;
+        while let Some(parent) = cur {
+            depth += 1;
+            cur = self.nodes[parent].parent;
         }
+        depth
     }
 
-    dir_sizes
+    /// Directories up to `max_depth` with their total sizes, like `du -d`.
+    fn du(&self, max_depth: Option<usize>, order: SortOrder) -> Vec<(NodeId, u64)> {
+        let sizes = self.sizes();
+        let mut out: Vec<(NodeId, u64)> = (0..self.nodes.len())
+            .filter(|id| self.is_dir(*id))
+            .filter(|id| max_depth.is_none_or(|d| self.depth(*id) <= d))
+            .map(|id| (id, sizes[id]))
+            .collect();
+
+        match order {
+            SortOrder::Size => out.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0))),
+            SortOrder::Path => out.sort_by_key(|(id, _)| self.path(*id)),
+        }
+        out
+    }
+
+    /// Nodes matching all given criteria, in path order. Directory sizes are total sizes.
+    fn find(&self, query: &FindQuery) -> Vec<(NodeId, u64)> {
+        let sizes = self.sizes();
+        let mut out: Vec<(NodeId, u64)> = (0..self.nodes.len())
+            .filter(|id| {
+                let node_type = if self.is_dir(*id) {
+                    NodeType::Dir
+                } else {
+                    NodeType::File
+                };
+                query.node_type.is_none_or(|t| t == node_type)
+                    && query
+                        .name
+                        .as_ref()
+                        .is_none_or(|p| glob_match(p, &self.nodes[*id].name))
+                    && query.min_size.is_none_or(|min| sizes[*id] >= min)
+                    && query.max_size.is_none_or(|max| sizes[*id] <= max)
+            })
+            .map(|id| (id, sizes[id]))
+            .collect();
+
+        out.sort_by_key(|(id, _)| self.path(*id));
+        out
+    }
+
+    /// The `n` largest files, largest first.
+    fn largest_files(&self, n: usize) -> Vec<(NodeId, u64)> {
+        self.find(&FindQuery {
+            node_type: Some(NodeType::File),
+            ..FindQuery::default()
+        })
+        .into_iter()
+        .map(|(id, size)| (std::cmp::Reverse(size), id))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .take(n)
+        .map(|(size, id)| (id, size.0))
+        .collect()
+    }
+
+    /// Renders the tree like the `tree` command, with human-readable sizes.
+    fn render_tree(&self) -> String {
+        let sizes = self.sizes();
+        let mut out = format!("/ ({})\n", human_size(sizes[FsTree::ROOT]));
+        self.render_children(FsTree::ROOT, "", &sizes, &mut out);
+        out
+    }
+
+    fn render_children(&self, dir: NodeId, prefix: &str, sizes: &[u64], out: &mut String) {
+        let children = match &self.nodes[dir].kind {
+            NodeKind::Dir { children } => children,
+            NodeKind::File { .. } => return,
+        };
+
+        for (idx, (name, id)) in children.iter().enumerate() {
+            let last = idx + 1 == children.len();
+            out.push_str(&format!(
+                "{}{}{}{} ({})\n",
+                prefix,
+                if last { "└── " } else { "├── " },
+                name,
+                if self.is_dir(*id) { "/" } else { "" },
+                human_size(sizes[*id])
+            ));
+            let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            self.render_children(*id, &child_prefix, sizes, out);
+        }
+    }
 }
 
-fn task1(dir_sizes: &Vec<u64>) -> u64 {
+fn task1(tree: &FsTree) -> u64 {
     let limit = 100_000;
-    dir_sizes.iter().filter(|&x| *x < limit).sum()
+    tree.dir_sizes().iter().filter(|&x| *x < limit).sum()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DiskModel {
+    capacity: u64,
+    required_free: u64,
+}
+
+impl Default for DiskModel {
+    fn default() -> Self {
+        DiskModel {
+            capacity: 70_000_000,
+            required_free: 30_000_000,
+        }
+    }
+}
+
+impl DiskModel {
+    /// Space that still has to be freed when `used` bytes are in use.
+    fn space_to_free(&self, used: u64) -> u64 {
+        self.required_free
+            .saturating_sub(self.capacity.saturating_sub(used))
+    }
+}
+
+impl FsTree {
+    /// The smallest directory whose deletion frees enough space.
+    fn smallest_deletion(&self, disk: &DiskModel) -> Option<(NodeId, u64)> {
+        let sizes = self.sizes();
+        let space_to_free = disk.space_to_free(sizes[FsTree::ROOT]);
+        (0..self.nodes.len())
+            .filter(|id| self.is_dir(*id) && sizes[*id] >= space_to_free)
+            .map(|id| (id, sizes[id]))
+            .min_by_key(|(id, size)| (*size, *id))
+    }
+
+    /// The set of directories, none inside another, with the smallest total size that still
+    /// frees enough space. Returns the directories in path order and their total size.
+    ///
+    /// A subset-sum over the tree: bottom-up, every directory gets the set of totals that can
+    /// be freed inside it, either by deleting it as a whole or by combining its
+    /// subdirectories. Only totals below the space to free are kept, plus the smallest one
+    /// that frees enough, since anything larger can only be worse. Totals that stay too small
+    /// even when everything not yet combined is deleted as well are dropped too. Fails if a
+    /// set would hold more than `max_totals` totals, or if all combinations together would take
+    /// more than a thousand sums per allowed total.
+    fn minimal_deletion_set(
+        &self,
+        disk: &DiskModel,
+        max_totals: usize,
+    ) -> io::Result<Option<(Vec<NodeId>, u64)>> {
+        let sizes = self.sizes();
+        let space_to_free = disk.space_to_free(sizes[FsTree::ROOT]);
+        if space_to_free == 0 {
+            return Ok(Some((Vec::new(), 0)));
+        }
+        if self.smallest_deletion(disk).is_none() {
+            return Ok(None);
+        }
+
+        let mut budget = max_totals.saturating_mul(1000);
+
+        // Per directory: the freeable totals, and the totals reachable with its first i
+        // subdirectories for backtracking
+        let mut freeable: Vec<SumSet> = vec![SumSet::new(); self.nodes.len()];
+        let mut prefixes: Vec<Vec<SumSet>> = vec![Vec::new(); self.nodes.len()];
+        for id in (0..self.nodes.len()).rev() {
+            if !self.is_dir(id) {
+                continue;
+            }
+
+            // Upper bound of what can still be freed outside of the totals combined so far
+            let children = self.subdirs(id);
+            let mut rest =
+                sizes[FsTree::ROOT] - sizes[id] + children.iter().map(|c| sizes[*c]).sum::<u64>();
+
+            let mut combined = SumSet::new();
+            combined.insert(0);
+            for child in children {
+                rest -= sizes[child];
+                let floor = space_to_free.saturating_sub(rest);
+                let sums = combined.totals.len() * freeable[child].totals.len();
+                if sums > budget {
+                    return Err(io::Error::other(format!(
+                        "more than {} sums needed to combine freeable totals, raise --max-totals",
+                        max_totals.saturating_mul(1000)
+                    )));
+                }
+                budget -= sums;
+
+                let next = combined.sumset(&freeable[child], floor, space_to_free, max_totals)?;
+                prefixes[id].push(combined);
+                combined = next;
+            }
+
+            combined.remove(0);
+            combined.insert(sizes[id]);
+            combined.prune(space_to_free.saturating_sub(rest), space_to_free);
+            freeable[id] = combined;
+        }
+
+        let Some(total) = freeable[FsTree::ROOT].first_from(space_to_free) else {
+            return Ok(None);
+        };
+        let mut dirs = Vec::new();
+        self.collect_deletions(FsTree::ROOT, total, &sizes, &freeable, &prefixes, &mut dirs);
+        dirs.sort_by_key(|id| self.path(*id));
+        Ok(Some((dirs, total)))
+    }
+
+    fn subdirs(&self, id: NodeId) -> Vec<NodeId> {
+        match &self.nodes[id].kind {
+            NodeKind::Dir { children } => children
+                .values()
+                .copied()
+                .filter(|c| self.is_dir(*c))
+                .collect(),
+            NodeKind::File { .. } => Vec::new(),
+        }
+    }
+
+    /// Backtracks which directories inside `id` make up the freeable `total`.
+    fn collect_deletions(
+        &self,
+        id: NodeId,
+        mut total: u64,
+        sizes: &[u64],
+        freeable: &[SumSet],
+        prefixes: &[Vec<SumSet>],
+        out: &mut Vec<NodeId>,
+    ) {
+        if total == sizes[id] {
+            out.push(id);
+            return;
+        }
+
+        for (idx, child) in self.subdirs(id).into_iter().enumerate().rev() {
+            let before = &prefixes[id][idx];
+            if before.contains(total) {
+                continue;
+            }
+            let part = freeable[child]
+                .iter()
+                .find(|part| *part <= total && before.contains(total - part))
+                .unwrap();
+            self.collect_deletions(child, part, sizes, freeable, prefixes, out);
+            total -= part;
+        }
+    }
+}
+
+/// A set of totals, kept sorted.
+#[derive(Debug, Clone, Default)]
+struct SumSet {
+    totals: Vec<u64>,
+}
+
+impl SumSet {
+    fn new() -> Self {
+        SumSet { totals: Vec::new() }
+    }
+
+    fn insert(&mut self, value: u64) {
+        if let Err(idx) = self.totals.binary_search(&value) {
+            self.totals.insert(idx, value);
+        }
+    }
+
+    fn remove(&mut self, value: u64) {
+        if let Ok(idx) = self.totals.binary_search(&value) {
+            self.totals.remove(idx);
+        }
+    }
+
+    fn contains(&self, value: u64) -> bool {
+        self.totals.binary_search(&value).is_ok()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.totals.iter().copied()
+    }
+
+    fn first_from(&self, value: u64) -> Option<u64> {
+        let idx = self.totals.partition_point(|t| *t < value);
+        self.totals.get(idx).copied()
+    }
+
+    /// Drops every total below `floor` and every total of at least `target` but the smallest
+    /// one.
+    fn prune(&mut self, floor: u64, target: u64) {
+        let idx = self.totals.partition_point(|t| *t < target);
+        self.totals.truncate(idx + 1);
+        self.totals.retain(|t| *t >= floor);
+    }
+
+    /// `self` together with every `a + b` for `a` in `self` and `b` in `other`, without totals
+    /// below `floor` and pruned at `target`. Fails once more than `max_totals` totals are left.
+    fn sumset(
+        &self,
+        other: &SumSet,
+        floor: u64,
+        target: u64,
+        max_totals: usize,
+    ) -> io::Result<SumSet> {
+        let too_many = || {
+            io::Error::other(format!(
+                "more than {} freeable totals to track, raise --max-totals",
+                max_totals
+            ))
+        };
+
+        let mut totals: Vec<u64> = self.iter().filter(|t| *t >= floor).collect();
+        // Smallest total of at least `target`, all larger ones are dropped right away
+        let mut enough = self.first_from(target);
+        for shift in other.iter() {
+            for sum in self.totals.iter().map(|t| t + shift) {
+                if sum < floor {
+                    continue;
+                } else if sum < target {
+                    totals.push(sum);
+                } else if enough.is_none_or(|e| sum < e) {
+                    enough = Some(sum);
+                }
+            }
+
+            if totals.len() > 2 * max_totals {
+                totals.sort_unstable();
+                totals.dedup();
+                if totals.len() > max_totals {
+                    return Err(too_many());
+                }
+            }
+        }
+
+        totals.retain(|t| *t < target);
+        totals.extend(enough);
+        totals.sort_unstable();
+        totals.dedup();
+        if totals.len() > max_totals {
+            return Err(too_many());
+        }
+        Ok(SumSet { totals })
+    }
+}
+
+fn task2(tree: &FsTree) -> u64 {
+    tree.smallest_deletion(&DiskModel::default()).unwrap().1
+}
+
+/// SplitMix64, so shuffled transcripts are reproducible from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TraversalOrder {
+    /// Entries sorted by name, subdirectories visited depth-first
+    DepthFirst,
+    /// Listings and visiting order shuffled; sometimes returns via `cd /` instead of `cd ..`
+    Shuffled,
 }
 
-fn task2(dir_sizes: &Vec<u64>) -> u64 {
-    let limit = 30000000 - (70000000 as i64 - i64::try_from(*dir_sizes.last().unwrap()).unwrap());
-    *dir_sizes
+enum Entry {
+    Dir(String),
+    File(String, u64),
+}
+
+/// Identifies a file with several hard links, so that it is only listed once.
+#[cfg(unix)]
+fn hard_link_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    if metadata.nlink() > 1 {
+        Some((metadata.dev(), metadata.ino()))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn hard_link_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Lists the regular files and directories of `dir`. Symlinks, special files and names that
+/// cannot be represented in a transcript are skipped with a warning. Hard links to a file in
+/// `linked` are skipped silently; other hard-linked files are added to it.
+fn read_entries(dir: &Path, linked: &mut HashSet<(u64, u64)>) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) if !name.contains('\n') => name,
+            _ => {
+                eprintln!("skipping {:?}: unsupported name", entry.path());
+                continue;
+            }
+        };
+
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() {
+            entries.push(Entry::Dir(name));
+        } else if metadata.is_file() {
+            if hard_link_id(&metadata).is_some_and(|id| !linked.insert(id)) {
+                continue;
+            }
+            entries.push(Entry::File(name, metadata.len()));
+        } else {
+            eprintln!("skipping {:?}: not a regular file", entry.path());
+        }
+    }
+
+    entries.sort_by(|a, b| entry_name(a).cmp(entry_name(b)));
+    Ok(entries)
+}
+
+fn entry_name(entry: &Entry) -> &str {
+    match entry {
+        Entry::Dir(name) | Entry::File(name, _) => name,
+    }
+}
+
+/// Walks a real directory and writes a `$ cd`/`$ ls` transcript of it that `FsTree::parse`
+/// reads back. Only regular files count towards sizes, and a file with several hard links is
+/// only listed under the first one found, like `du` does. Note that `du -b` additionally
+/// counts the directory entries themselves.
+fn generate_transcript<W: Write>(
+    root: &Path,
+    order: TraversalOrder,
+    seed: u64,
+    out: &mut W,
+) -> io::Result<()> {
+    let mut rng = Rng(seed);
+    writeln!(out, "$ cd /")?;
+    walk(
+        root,
+        &mut Vec::new(),
+        &mut HashSet::new(),
+        order,
+        &mut rng,
+        out,
+    )
+}
+
+/// Lists `dir`, whose path below the root is `components`, and visits its subdirectories.
+/// Ends in `dir` again.
+fn walk<W: Write>(
+    dir: &Path,
+    components: &mut Vec<String>,
+    linked: &mut HashSet<(u64, u64)>,
+    order: TraversalOrder,
+    rng: &mut Rng,
+    out: &mut W,
+) -> io::Result<()> {
+    let mut entries = read_entries(dir, linked)?;
+    if order == TraversalOrder::Shuffled {
+        rng.shuffle(&mut entries);
+    }
+
+    writeln!(out, "$ ls")?;
+    for entry in &entries {
+        match entry {
+            Entry::Dir(name) => writeln!(out, "dir {}", name)?,
+            Entry::File(name, size) => writeln!(out, "{} {}", size, name)?,
+        }
+    }
+
+    let mut subdirs: Vec<&String> = entries
         .iter()
-        .filter(|&x| *x >= (limit as u64))
-        .min()
-        .unwrap()
+        .filter_map(|e| match e {
+            Entry::Dir(name) => Some(name),
+            Entry::File(..) => None,
+        })
+        .collect();
+    if order == TraversalOrder::Shuffled {
+        rng.shuffle(&mut subdirs);
+    }
+
+    for name in subdirs {
+        writeln!(out, "$ cd {}", name)?;
+        components.push(name.clone());
+        walk(&dir.join(name), components, linked, order, rng, out)?;
+        components.pop();
+
+        if order == TraversalOrder::Shuffled && rng.next_u64().is_multiple_of(4) {
+            // Go back the long way round
+            writeln!(out, "$ cd /")?;
+            for component in components.iter() {
+                writeln!(out, "$ cd {}", component)?;
+            }
+        } else {
+            writeln!(out, "$ cd ..")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn usage_error(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg.to_string())
+}
+
+fn parse_number_arg(value: Option<String>, flag: &str) -> io::Result<u64> {
+    value
+        .and_then(|v| v.parse::<u64>().ok())
+        .ok_or_else(|| usage_error(&format!("{} expects a number", flag)))
+}
+
+fn run_command(
+    tree: &FsTree,
+    command: &str,
+    mut args: impl Iterator<Item = String>,
+) -> io::Result<()> {
+    match command {
+        "du" => {
+            let mut max_depth = None;
+            let mut order = SortOrder::Path;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--depth" => {
+                        max_depth = Some(parse_number_arg(args.next(), "--depth")? as usize)
+                    }
+                    "--sort" => {
+                        order = match args.next().as_deref() {
+                            Some("size") => SortOrder::Size,
+                            Some("path") => SortOrder::Path,
+                            _ => return Err(usage_error("--sort expects size or path")),
+                        }
+                    }
+                    _ => return Err(usage_error(&format!("unknown du option {:?}", arg))),
+                }
+            }
+            for (id, size) in tree.du(max_depth, order) {
+                println!("{}\t{}", size, tree.path(id));
+            }
+        }
+        "find" => {
+            let mut query = FindQuery::default();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--name" => {
+                        query.name = Some(
+                            args.next()
+                                .ok_or_else(|| usage_error("--name expects a pattern"))?,
+                        )
+                    }
+                    "--min-size" => {
+                        query.min_size = Some(parse_number_arg(args.next(), "--min-size")?)
+                    }
+                    "--max-size" => {
+                        query.max_size = Some(parse_number_arg(args.next(), "--max-size")?)
+                    }
+                    "--type" => {
+                        query.node_type = match args.next().as_deref() {
+                            Some("f") => Some(NodeType::File),
+                            Some("d") => Some(NodeType::Dir),
+                            _ => return Err(usage_error("--type expects f or d")),
+                        }
+                    }
+                    _ => return Err(usage_error(&format!("unknown find option {:?}", arg))),
+                }
+            }
+            for (id, size) in tree.find(&query) {
+                println!("{}\t{}", size, tree.path(id));
+            }
+        }
+        "tree" => print!("{}", tree.render_tree()),
+        "largest" => {
+            let n = match args.next() {
+                Some(n) => parse_number_arg(Some(n), "largest")? as usize,
+                None => 10,
+            };
+            for (id, size) in tree.largest_files(n) {
+                println!("{}\t{}", size, tree.path(id));
+            }
+        }
+        "plan" => {
+            let mut disk = DiskModel::default();
+            let mut max_totals = 1_000_000;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--capacity" => disk.capacity = parse_number_arg(args.next(), "--capacity")?,
+                    "--required" => {
+                        disk.required_free = parse_number_arg(args.next(), "--required")?
+                    }
+                    "--max-totals" => {
+                        max_totals = parse_number_arg(args.next(), "--max-totals")? as usize
+                    }
+                    _ => return Err(usage_error(&format!("unknown plan option {:?}", arg))),
+                }
+            }
+
+            let used = tree.sizes()[FsTree::ROOT];
+            println!(
+                "Used {} of {}, need to free {}",
+                used,
+                disk.capacity,
+                disk.space_to_free(used)
+            );
+            match tree.smallest_deletion(&disk) {
+                Some((id, size)) => println!("Smallest directory: {}\t{}", size, tree.path(id)),
+                None => println!("Smallest directory: none frees enough space"),
+            }
+            match tree.minimal_deletion_set(&disk, max_totals)? {
+                Some((dirs, total)) => {
+                    println!("Smallest set of directories: {} in total", total);
+                    let sizes = tree.sizes();
+                    for id in dirs {
+                        println!("{}\t{}", sizes[id], tree.path(id));
+                    }
+                }
+                None => println!("Smallest set of directories: none frees enough space"),
+            }
+        }
+        _ => return Err(usage_error(&format!("unknown command {:?}", command))),
+    }
+
+    Ok(())
 }
 
 fn main() -> io::Result<()> {
-    let data = load_data("input.txt")?;
-    let dir_sizes = collect_dir_sizes(&data);
+    // Usage: day07 [path] [command]
+    //
+    // Commands:
+    //   du [--depth N] [--sort size|path]
+    //   find [--name GLOB] [--min-size N] [--max-size N] [--type f|d]
+    //   tree
+    //   largest [N]
+    //   plan [--capacity N] [--required N] [--max-totals N]
+    //
+    // Or, to write a transcript of a real directory to stdout:
+    //   day07 generate <dir> [--order dfs|shuffled] [--seed N]
+    const COMMANDS: [&str; 5] = ["du", "find", "tree", "largest", "plan"];
+
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(|a| a.as_str()) == Some("generate") {
+        args.next();
+        let dir = args
+            .next()
+            .ok_or_else(|| usage_error("generate expects a directory"))?;
+        let mut order = TraversalOrder::DepthFirst;
+        let mut seed = 0;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--order" => {
+                    order = match args.next().as_deref() {
+                        Some("dfs") => TraversalOrder::DepthFirst,
+                        Some("shuffled") => TraversalOrder::Shuffled,
+                        _ => return Err(usage_error("--order expects dfs or shuffled")),
+                    }
+                }
+                "--seed" => seed = parse_number_arg(args.next(), "--seed")?,
+                _ => return Err(usage_error(&format!("unknown generate option {:?}", arg))),
+            }
+        }
+
+        let mut out = io::BufWriter::new(io::stdout().lock());
+        generate_transcript(Path::new(&dir), order, seed, &mut out)?;
+        return out.flush();
+    }
+
+    let path = match args.peek() {
+        Some(arg) if !COMMANDS.contains(&arg.as_str()) => args.next().unwrap(),
+        _ => String::from("input.txt"),
+    };
+    let data = load_data(&path)?;
+    let tree = FsTree::parse(&data)?;
+
+    if let Some(command) = args.next() {
+        return run_command(&tree, &command, args);
+    }
 
     // Task 1
-    let out_task1 = task1(&dir_sizes);
+    let out_task1 = task1(&tree);
     println!("Task 1: {}", out_task1);
 
     // Task 2
-    let out_task2 = task2(&dir_sizes);
+    let out_task2 = task2(&tree);
     println!("Task 2: {}", out_task2);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(transcript: &str) -> FsTree {
+        let data: Vec<String> = transcript.lines().map(String::from).collect();
+        FsTree::parse(&data).unwrap()
+    }
+
+    /// Runs the planner with `space_to_free` bytes still to be freed.
+    fn plan(tree: &FsTree, space_to_free: u64) -> Option<(Vec<String>, u64)> {
+        let disk = DiskModel {
+            capacity: tree.sizes()[FsTree::ROOT],
+            required_free: space_to_free,
+        };
+        let (dirs, total) = tree.minimal_deletion_set(&disk, 1_000_000).unwrap()?;
+        Some((dirs.iter().map(|id| tree.path(*id)).collect(), total))
+    }
+
+    // /a (120) holds /a/b (30) and /a/c (40) plus 50 bytes of its own, /d holds 70
+    const NESTED: &str = "$ cd /\n$ ls\ndir a\ndir d\n$ cd a\n$ ls\ndir b\ndir c\n50 x\n\
+                          $ cd b\n$ ls\n30 y\n$ cd ..\n$ cd c\n$ ls\n40 z\n$ cd /\n$ cd d\n$ ls\n70 w";
+
+    #[test]
+    fn example_deletes_single_directory() {
+        let tree = parse(&fs::read_to_string("example.txt").unwrap());
+        let disk = DiskModel::default();
+        let (dirs, total) = tree
+            .minimal_deletion_set(&disk, 1_000_000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(dirs, vec![tree.child(FsTree::ROOT, "d").unwrap()]);
+        assert_eq!(total, 24933642);
+    }
+
+    #[test]
+    fn combines_nested_candidates() {
+        let tree = parse(NESTED);
+        assert_eq!(
+            plan(&tree, 100),
+            Some((vec!["/a/b".into(), "/d".into()], 100))
+        );
+        assert_eq!(
+            plan(&tree, 110),
+            Some((vec!["/a/c".into(), "/d".into()], 110))
+        );
+        assert_eq!(plan(&tree, 115), Some((vec!["/a".into()], 120)));
+        assert_eq!(
+            plan(&tree, 121),
+            Some((vec!["/a/b".into(), "/a/c".into(), "/d".into()], 140))
+        );
+        assert_eq!(plan(&tree, 141), Some((vec!["/".into()], 190)));
+    }
+
+    #[test]
+    fn no_deletion_needed() {
+        let tree = parse(NESTED);
+        assert_eq!(plan(&tree, 0), Some((Vec::new(), 0)));
+    }
+
+    #[test]
+    fn nothing_frees_enough() {
+        let tree = parse(NESTED);
+        assert_eq!(plan(&tree, 191), None);
+    }
+
+    #[test]
+    fn too_many_totals_is_an_error() {
+        let tree = parse(NESTED);
+        let disk = DiskModel {
+            capacity: 190,
+            required_free: 121,
+        };
+        assert!(tree.minimal_deletion_set(&disk, 1).is_err());
+    }
+
+    /// Smallest total of directories, none inside another, that frees `space_to_free`.
+    fn brute_force(tree: &FsTree, space_to_free: u64) -> Option<u64> {
+        let sizes = tree.sizes();
+        let dirs: Vec<NodeId> = (0..tree.nodes.len())
+            .filter(|id| tree.is_dir(*id))
+            .collect();
+        let inside = |id: NodeId, dir: NodeId| {
+            let mut cur = tree.nodes[id].parent;
+            while let Some(node) = cur {
+                if node == dir {
+                    return true;
+                }
+                cur = tree.nodes[node].parent;
+            }
+            false
+        };
+
+        (0u32..1 << dirs.len())
+            .filter_map(|mask| {
+                let chosen: Vec<NodeId> = (0..dirs.len())
+                    .filter(|i| mask & (1 << i) != 0)
+                    .map(|i| dirs[i])
+                    .collect();
+                let nested = chosen
+                    .iter()
+                    .any(|a| chosen.iter().any(|b| a != b && inside(*a, *b)));
+                let total = chosen.iter().map(|id| sizes[*id]).sum::<u64>();
+                (!nested && total >= space_to_free).then_some(total)
+            })
+            .min()
+    }
+
+    #[test]
+    fn matches_brute_force_on_random_trees() {
+        let mut rng = Rng(7);
+        for _ in 0..200 {
+            let mut tree = FsTree::new();
+            let mut dirs = vec![FsTree::ROOT];
+            for i in 0..(rng.next_u64() % 10) as usize {
+                let parent = dirs[(rng.next_u64() % dirs.len() as u64) as usize];
+                dirs.push(tree.add(
+                    parent,
+                    &format!("d{}", i),
+                    NodeKind::Dir {
+                        children: BTreeMap::new(),
+                    },
+                ));
+            }
+            for (i, dir) in dirs.clone().into_iter().enumerate() {
+                let size = rng.next_u64() % 50;
+                tree.add(dir, &format!("f{}", i), NodeKind::File { size });
+            }
+
+            let root_size = tree.sizes()[FsTree::ROOT];
+            let space_to_free = rng.next_u64() % (root_size + 2);
+            let planned = plan(&tree, space_to_free);
+            assert_eq!(
+                planned.as_ref().map(|(_, total)| *total),
+                brute_force(&tree, space_to_free)
+            );
+
+            if let Some((paths, total)) = planned {
+                let sizes = tree.sizes();
+                let planned_total = (0..tree.nodes.len())
+                    .filter(|id| paths.contains(&tree.path(*id)))
+                    .map(|id| sizes[id])
+                    .sum::<u64>();
+                assert_eq!(planned_total, total);
+                for a in &paths {
+                    assert!(!paths.iter().any(|b| b.starts_with(&format!("{}/", a))));
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::cmp;
+use std::env;
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Error, ErrorKind, Write};
 use std::path::Path;
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -11,108 +12,456 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
+/// Reads a grid of tree heights. Every row must have the same number of trees.
 fn load_data(filename: &str) -> io::Result<Vec<Vec<i32>>> {
-    Ok(read_lines(filename)?
-        .map(|l| {
-            l.unwrap()
-                .chars()
-                .map(|c| (c as i32) - ('0' as i32))
-                .collect()
-        })
-        .collect())
-}
-
-fn task1(data: &Vec<Vec<i32>>) -> usize {
-    let end_row = data.len();
-    let end_col = data[0].len();
-
-    let mut max_height_down_right = vec![vec![(0, 0); end_col]; end_row]; // (down, right)
-    for row in (1..end_row - 1).rev() {
-        for col in (1..end_col - 1).rev() {
-            // Check row downards
-            max_height_down_right[row][col].0 =
-                cmp::max(data[row + 1][col], max_height_down_right[row + 1][col].0);
-
-            // Check col rightwards
-            max_height_down_right[row][col].1 =
-                cmp::max(data[row][col + 1], max_height_down_right[row][col + 1].1);
+    let mut data: Vec<Vec<i32>> = Vec::new();
+    for (idx, line) in read_lines(filename)?.enumerate() {
+        let row = line?
+            .chars()
+            .map(|c| {
+                c.to_digit(10).map(|h| h as i32).ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("line {}: invalid tree height {:?}", idx + 1, c),
+                    )
+                })
+            })
+            .collect::<io::Result<Vec<i32>>>()?;
+
+        if let Some(first) = data.first() {
+            if row.len() != first.len() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "line {}: expected {} trees like line 1, found {}",
+                        idx + 1,
+                        first.len(),
+                        row.len()
+                    ),
+                ));
+            }
         }
+        data.push(row);
     }
+    Ok(data)
+}
 
-    let mut out = 2 * (end_row + end_col) - 4;
+/// Row and column steps of the directions trees can look in. The first four are the cardinal
+/// directions, the last four the diagonals.
+const DIRECTIONS: [(isize, isize); 8] = [
+    (-1, 0),  // up
+    (1, 0),   // down
+    (0, -1),  // left
+    (0, 1),   // right
+    (-1, -1), // up-left
+    (-1, 1),  // up-right
+    (1, -1),  // down-left
+    (1, 1),   // down-right
+];
 
-    let mut max_height_up_left = vec![vec![(0, 0); end_col]; end_row]; // (up, left)
-    for row in 1..end_row-1 {
-        for col in 1..end_col-1 {
-            // Check row upwards
-            max_height_up_left[row][col].0 =
-                cmp::max(data[row - 1][col], max_height_up_left[row - 1][col].0);
+/// How the trees are looked at: along the cardinal directions only or also diagonally, and
+/// with the viewer's eyes `viewer_height` above the top of the tree it stands on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Sight {
+    diagonals: bool,
+    viewer_height: i32,
+}
 
-            // Check col leftwards
-            max_height_up_left[row][col].1 =
-                cmp::max(data[row][col - 1], max_height_up_left[row][col - 1].1);
+impl Sight {
+    fn directions(&self) -> &'static [(isize, isize)] {
+        if self.diagonals {
+            &DIRECTIONS
+        } else {
+            &DIRECTIONS[..4]
+        }
+    }
+}
+
+/// All lines of sight of a `rows` x `cols` grid in `direction`. Every line starts at the edge
+/// the direction points to and walks away from it, so the cells a tree looks at come before
+/// the tree itself.
+fn sight_lines(rows: usize, cols: usize, direction: (isize, isize)) -> Vec<Vec<(usize, usize)>> {
+    let in_grid = |row: isize, col: isize| {
+        row >= 0 && col >= 0 && (row as usize) < rows && (col as usize) < cols
+    };
 
-            let tree_height = data[row][col];
-            if tree_height > max_height_up_left[row][col].0
-                || tree_height > max_height_up_left[row][col].1
-                || tree_height > max_height_down_right[row][col].0
-                || tree_height > max_height_down_right[row][col].1
-            {
-                out += 1;
+    let mut lines = Vec::new();
+    for row in 0..rows as isize {
+        for col in 0..cols as isize {
+            if in_grid(row + direction.0, col + direction.1) {
+                continue;
             }
+
+            let mut line = Vec::new();
+            let (mut r, mut c) = (row, col);
+            while in_grid(r, c) {
+                line.push((r as usize, c as usize));
+                r -= direction.0;
+                c -= direction.1;
+            }
+            lines.push(line);
         }
     }
+    lines
+}
+
+/// The directions from which a tree can be seen from outside the grid, as bit flags. Bit `i`
+/// stands for `DIRECTIONS[i]`, so up, down, left and right are 1, 2, 4 and 8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Visibility(u8);
+
+impl Visibility {
+    fn is_visible(self) -> bool {
+        self.0 != 0
+    }
 
-    out
+    fn directions(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    fn cardinal(self) -> u8 {
+        self.0 & 0xf
+    }
 }
 
-fn task2(data: &Vec<Vec<i32>>) -> usize {
-    let mut out = 0;
+/// Computes for every tree in which directions it can see the edge of the grid, i.e. all trees
+/// between it and the edge are lower than the viewer's eyes.
+fn visibility(data: &[Vec<i32>], sight: &Sight) -> Vec<Vec<Visibility>> {
+    let rows = data.len();
+    let cols = data.first().map_or(0, |r| r.len());
+    let mut out = vec![vec![Visibility::default(); cols]; rows];
 
-    for i in 1..data.len() - 1 {
-        for j in 1..data[i].len() - 1 {
-            // Top
-            let mut top = i - 1;
-            while top > 0 && data[top][j] < data[i][j] {
-                top -= 1;
+    for (bit, direction) in sight.directions().iter().enumerate() {
+        for line in sight_lines(rows, cols, *direction) {
+            // Tallest tree between the current one and the edge
+            let mut max_height: Option<i32> = None;
+            for (row, col) in line {
+                let tree_height = data[row][col];
+                if max_height.is_none_or(|m| tree_height + sight.viewer_height > m) {
+                    out[row][col].0 |= 1 << bit;
+                }
+                max_height = Some(max_height.map_or(tree_height, |m| cmp::max(m, tree_height)));
             }
+        }
+    }
 
-            // Down
-            let mut down = i + 1;
-            while down + 1 < data.len() && data[down][j] < data[i][j] {
-                down += 1;
-            }
+    out
+}
+
+fn task1(data: &[Vec<i32>], sight: &Sight) -> usize {
+    visibility(data, sight)
+        .iter()
+        .flatten()
+        .filter(|v| v.is_visible())
+        .count()
+}
 
-            // Left
-            let mut left = j - 1;
-            while left > 0 && data[i][left] < data[i][j] {
-                left -= 1;
+/// Scenic score of every tree, indexed like the height grid.
+struct ScenicScores {
+    scores: Vec<Vec<usize>>,
+}
+
+impl ScenicScores {
+    /// The highest score and its `(row, col)`. Ties go to the first tree in reading order.
+    fn best(&self) -> Option<(usize, (usize, usize))> {
+        let mut best: Option<(usize, (usize, usize))> = None;
+        for (row, scores) in self.scores.iter().enumerate() {
+            for (col, score) in scores.iter().enumerate() {
+                if best.is_none_or(|(b, _)| *score > b) {
+                    best = Some((*score, (row, col)));
+                }
             }
+        }
+        best
+    }
+}
+
+/// Viewing distance of every tree of `line` when looking towards index 0 with the eyes
+/// `viewer_height` above the tree. The view is blocked by the first tree at least as high as
+/// the eyes.
+///
+/// A monotonic stack keeps the trees that are not hidden behind a later tree of the same or
+/// greater height. Only those can block a view, and their heights strictly decrease towards
+/// the top, so the nearest blocker is found by binary search. With single digit heights the
+/// stack never holds more than ten trees.
+fn viewing_distances(line: &[i32], viewer_height: i32) -> Vec<usize> {
+    let mut out = Vec::with_capacity(line.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for (idx, height) in line.iter().enumerate() {
+        let eyes = height + viewer_height;
+        let blockers = stack.partition_point(|s| line[*s] >= eyes);
+        out.push(match blockers {
+            0 => idx,
+            n => idx - stack[n - 1],
+        });
+
+        while stack.last().is_some_and(|top| line[*top] <= *height) {
+            stack.pop();
+        }
+        stack.push(idx);
+    }
+    out
+}
 
-            // Right
-            let mut right = j + 1;
-            while right + 1 < data[i].len() && data[i][right] < data[i][j] {
-                right += 1;
+fn task2(data: &[Vec<i32>], sight: &Sight) -> ScenicScores {
+    let rows = data.len();
+    let cols = data.first().map_or(0, |r| r.len());
+    let mut scores = vec![vec![1; cols]; rows];
+
+    for direction in sight.directions() {
+        for line in sight_lines(rows, cols, *direction) {
+            let heights: Vec<i32> = line.iter().map(|(r, c)| data[*r][*c]).collect();
+            let distances = viewing_distances(&heights, sight.viewer_height);
+            for ((row, col), distance) in line.into_iter().zip(distances) {
+                scores[row][col] *= distance;
             }
+        }
+    }
+
+    ScenicScores { scores }
+}
+
+fn print_scores(scores: &ScenicScores) {
+    let width = scores
+        .scores
+        .iter()
+        .flatten()
+        .max()
+        .map_or(1, |s| s.to_string().len());
+    for row in &scores.scores {
+        let cells: Vec<String> = row.iter().map(|s| format!("{:>width$}", s)).collect();
+        println!("{}", cells.join(" "));
+    }
+}
 
-            let score = (i - top) * (down - i) * (j - left) * (right - j);
-            out = cmp::max(out, score);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Visibility,
+    Scores,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HeatmapFormat {
+    /// Plain grayscale PGM (`P2`).
+    Pgm,
+    /// Plain colour PPM (`P3`).
+    Ppm,
+    /// One character per tree on a coloured terminal background.
+    Ascii,
+}
+
+/// A grid of values to colour, plus the character shown for every tree in ASCII output.
+struct Heatmap {
+    values: Vec<Vec<u64>>,
+    labels: Vec<Vec<char>>,
+}
+
+impl Heatmap {
+    /// Values are the number of directions a tree is visible from. Labels are the cardinal
+    /// visibility bit flags as a hex digit, `.` for trees hidden in all cardinal directions.
+    fn visibility(grid: &[Vec<Visibility>]) -> Heatmap {
+        Heatmap {
+            values: grid
+                .iter()
+                .map(|r| r.iter().map(|v| v.directions() as u64).collect())
+                .collect(),
+            labels: grid
+                .iter()
+                .map(|r| {
+                    r.iter()
+                        .map(|v| match v.cardinal() {
+                            0 => '.',
+                            flags => char::from_digit(flags as u32, 16).unwrap(),
+                        })
+                        .collect()
+                })
+                .collect(),
         }
     }
 
-    out
+    /// Values are the scenic scores. Labels are the tree heights.
+    fn scores(data: &[Vec<i32>], scores: &ScenicScores) -> Heatmap {
+        Heatmap {
+            values: scores
+                .scores
+                .iter()
+                .map(|r| r.iter().map(|s| *s as u64).collect())
+                .collect(),
+            labels: data
+                .iter()
+                .map(|r| {
+                    r.iter()
+                        .map(|h| char::from_digit(*h as u32, 10).unwrap_or('?'))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Scales every value linearly to `0..=255`.
+    fn levels(&self) -> Vec<Vec<u8>> {
+        let max = self.values.iter().flatten().max().copied().unwrap_or(0);
+        self.values
+            .iter()
+            .map(|r| {
+                r.iter()
+                    .map(|v| (v * 255).checked_div(max).unwrap_or(0) as u8)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn write<W: Write>(&self, out: &mut W, format: HeatmapFormat) -> io::Result<()> {
+        let levels = self.levels();
+        let rows = levels.len();
+        let cols = levels.first().map_or(0, |r| r.len());
+
+        match format {
+            HeatmapFormat::Pgm => {
+                writeln!(out, "P2\n{} {}\n255", cols, rows)?;
+                for row in &levels {
+                    let cells: Vec<String> = row.iter().map(|l| l.to_string()).collect();
+                    writeln!(out, "{}", cells.join(" "))?;
+                }
+            }
+            HeatmapFormat::Ppm => {
+                writeln!(out, "P3\n{} {}\n255", cols, rows)?;
+                for row in &levels {
+                    let cells: Vec<String> = row
+                        .iter()
+                        .map(|l| {
+                            let (r, g, b) = heat_colour(*l);
+                            format!("{} {} {}", r, g, b)
+                        })
+                        .collect();
+                    writeln!(out, "{}", cells.join("  "))?;
+                }
+            }
+            HeatmapFormat::Ascii => {
+                for (row, labels) in levels.iter().zip(&self.labels) {
+                    for (level, label) in row.iter().zip(labels) {
+                        let (r, g, b) = heat_colour(*level);
+                        write!(out, "\x1b[30;48;2;{};{};{}m{}", r, g, b, label)?;
+                    }
+                    writeln!(out, "\x1b[0m")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps a level to a colour on a blue - green - red ramp.
+fn heat_colour(level: u8) -> (u8, u8, u8) {
+    if level < 128 {
+        (0, level * 2, 255 - level * 2)
+    } else {
+        let up = (level - 128) * 2;
+        (up, 255 - up, 0)
+    }
+}
+
+fn parse_layer(value: Option<String>) -> io::Result<Layer> {
+    match value.as_deref() {
+        Some("visibility") => Ok(Layer::Visibility),
+        Some("scores") => Ok(Layer::Scores),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "--heatmap expects visibility or scores",
+        )),
+    }
+}
+
+fn parse_format(value: Option<String>) -> io::Result<HeatmapFormat> {
+    match value.as_deref() {
+        Some("pgm") => Ok(HeatmapFormat::Pgm),
+        Some("ppm") => Ok(HeatmapFormat::Ppm),
+        Some("ascii") => Ok(HeatmapFormat::Ascii),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "--format expects pgm, ppm or ascii",
+        )),
+    }
+}
+
+/// Returns whether diagonals are included.
+fn parse_directions(value: Option<String>) -> io::Result<bool> {
+    match value.as_deref() {
+        Some("4") => Ok(false),
+        Some("8") => Ok(true),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "--directions expects 4 or 8",
+        )),
+    }
+}
+
+fn parse_viewer_height(value: Option<String>) -> io::Result<i32> {
+    value
+        .and_then(|v| v.parse::<i32>().ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "--viewer-height expects a number"))
 }
 
 fn main() -> io::Result<()> {
-    let data = load_data("input.txt")?;
+    // Usage: day08 [path] [--directions 4|8] [--viewer-height N] [--scores]
+    //              [--heatmap visibility|scores] [--format pgm|ppm|ascii] [--out PATH]
+    let mut path = String::from("input.txt");
+    let mut show_scores = false;
+    let mut heatmap = None;
+    let mut format = HeatmapFormat::Ascii;
+    let mut out_path = None;
+    let mut sight = Sight::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--directions" => sight.diagonals = parse_directions(args.next())?,
+            "--viewer-height" => sight.viewer_height = parse_viewer_height(args.next())?,
+            "--scores" => show_scores = true,
+            "--heatmap" => heatmap = Some(parse_layer(args.next())?),
+            "--format" => format = parse_format(args.next())?,
+            "--out" => {
+                out_path =
+                    Some(args.next().ok_or_else(|| {
+                        Error::new(ErrorKind::InvalidInput, "--out expects a path")
+                    })?)
+            }
+            _ => path = arg,
+        }
+    }
+
+    let data = load_data(&path)?;
+
+    if let Some(layer) = heatmap {
+        let map = match layer {
+            Layer::Visibility => Heatmap::visibility(&visibility(&data, &sight)),
+            Layer::Scores => Heatmap::scores(&data, &task2(&data, &sight)),
+        };
+        return match out_path {
+            Some(p) => map.write(&mut io::BufWriter::new(File::create(p)?), format),
+            None => map.write(&mut io::stdout().lock(), format),
+        };
+    }
 
     // Task 1
-    let out_task1 = task1(&data);
+    let out_task1 = task1(&data, &sight);
     println!("Task 1: {}", out_task1);
 
     // Task 2
-    let out_task2 = task2(&data);
-    println!("Task 2: {}", out_task2);
+    let scores = task2(&data, &sight);
+    let best = scores.best();
+    println!("Task 2: {}", best.map_or(0, |b| b.0));
+
+    if show_scores {
+        if let Some((_, (row, col))) = best {
+            println!(
+                "Best tree: row {}, column {} (height {})",
+                row, col, data[row][col]
+            );
+        }
+        print_scores(&scores);
+    }
 
     Ok(())
 }
@@ -1,5 +1,8 @@
 use regex::Regex;
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::env;
+use std::fmt;
 use std::fs::File;
 use std::hash::Hash;
 use std::io::{self, BufRead, Error, ErrorKind};
@@ -14,12 +17,64 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Direction {
     Left,
     Up,
     Right,
     Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    const CARDINAL: [Direction; 4] = [
+        Direction::Right,
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+    ];
+    const ALL: [Direction; 8] = [
+        Direction::Right,
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+        Direction::UpRight,
+        Direction::UpLeft,
+        Direction::DownLeft,
+        Direction::DownRight,
+    ];
+
+    /// The `(row, col)` offset of one step, rows grow downwards.
+    fn step(self) -> (i32, i32) {
+        match self {
+            Direction::Left => (0, -1),
+            Direction::Up => (-1, 0),
+            Direction::Right => (0, 1),
+            Direction::Down => (1, 0),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (-1, 1),
+            Direction::DownLeft => (1, -1),
+            Direction::DownRight => (1, 1),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Direction::Left => "L",
+            Direction::Up => "U",
+            Direction::Right => "R",
+            Direction::Down => "D",
+            Direction::UpLeft => "UL",
+            Direction::UpRight => "UR",
+            Direction::DownLeft => "DL",
+            Direction::DownRight => "DR",
+        })
+    }
 }
 
 impl FromStr for Direction {
@@ -31,7 +86,11 @@ impl FromStr for Direction {
             "U" => Ok(Direction::Up),
             "R" => Ok(Direction::Right),
             "D" => Ok(Direction::Down),
-            _ => Err(Error::new(ErrorKind::Other, "Parsing error")),
+            "UL" => Ok(Direction::UpLeft),
+            "UR" => Ok(Direction::UpRight),
+            "DL" => Ok(Direction::DownLeft),
+            "DR" => Ok(Direction::DownRight),
+            _ => Err(io::Error::other("Parsing error")),
         }
     }
 }
@@ -42,20 +101,34 @@ struct Instruction {
     n: u32,
 }
 
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.direction, self.n)
+    }
+}
+
 fn load_data(filename: &str) -> io::Result<Vec<Instruction>> {
-    let instruction_regex = Regex::new(r"(?P<direction>[RLUD]) (?P<num>\d+)").unwrap();
+    let instruction_regex =
+        Regex::new(r"^(?P<direction>UL|UR|DL|DR|[RLUD]) (?P<num>\d+)$").unwrap();
 
-    Ok(read_lines(filename)?
-        .map(|l| {
-            let s = l.unwrap();
-            let caps = instruction_regex.captures(&s).unwrap();
+    read_lines(filename)?
+        .enumerate()
+        .map(|(idx, l)| {
+            let s = l?;
+            let err = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("line {}: invalid instruction {:?}", idx + 1, s),
+                )
+            };
+            let caps = instruction_regex.captures(&s).ok_or_else(err)?;
 
-            Instruction {
-                direction: caps["direction"].parse::<Direction>().unwrap(),
-                n: caps["num"].parse::<u32>().unwrap(),
-            }
+            Ok(Instruction {
+                direction: caps["direction"].parse::<Direction>()?,
+                n: caps["num"].parse::<u32>().map_err(|_| err())?,
+            })
         })
-        .collect())
+        .collect()
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -68,121 +141,631 @@ impl Position {
     fn new() -> Self {
         Position { row: 0, col: 0 }
     }
+}
 
-    fn is_touching(&self, other: &Position) -> bool {
-        (self.row - 1 <= other.row)
-            && (other.row <= self.row + 1)
-            && (self.col - 1 <= other.col)
-            && (other.col <= self.col + 1)
+/// How the distance between two knots is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    /// Diagonal neighbours are one apart, as in the puzzle.
+    Chebyshev,
+    /// Diagonal neighbours are two apart.
+    Manhattan,
+}
+
+impl Metric {
+    fn distance(self, a: &Position, b: &Position) -> i32 {
+        let (rows, cols) = ((a.row - b.row).abs(), (a.col - b.col).abs());
+        match self {
+            Metric::Chebyshev => rows.max(cols),
+            Metric::Manhattan => rows + cols,
+        }
+    }
+}
+
+/// When a knot has to follow the one in front of it: only once it is more than `slack` away
+/// under `metric`. The puzzle's rope has a slack of 1 under the Chebyshev metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RopeModel {
+    slack: i32,
+    metric: Metric,
+}
+
+impl Default for RopeModel {
+    fn default() -> Self {
+        RopeModel {
+            slack: 1,
+            metric: Metric::Chebyshev,
+        }
+    }
+}
+
+impl RopeModel {
+    fn is_touching(&self, a: &Position, b: &Position) -> bool {
+        self.metric.distance(a, b) <= self.slack
+    }
+
+    /// Where `knot` ends up after `front` moved. If they are no longer touching, under the
+    /// Chebyshev metric it moves by one cell, straight or diagonally. Under the Manhattan
+    /// metric, where a diagonal step would overshoot, it moves straight along the axis with
+    /// the larger gap until it is touching again.
+    fn follow(&self, front: &Position, knot: &Position) -> Position {
+        let mut knot = *knot;
+        match self.metric {
+            Metric::Chebyshev if !self.is_touching(front, &knot) => {
+                knot.row += (front.row - knot.row).signum();
+                knot.col += (front.col - knot.col).signum();
+            }
+            Metric::Chebyshev => {}
+            Metric::Manhattan => {
+                while !self.is_touching(front, &knot) {
+                    let (rows, cols) = (front.row - knot.row, front.col - knot.col);
+                    if rows.abs() >= cols.abs() {
+                        knot.row += rows.signum();
+                    } else {
+                        knot.col += cols.signum();
+                    }
+                }
+            }
+        }
+        knot
     }
 }
 
 #[derive(Debug)]
 struct Rope {
     knots: Vec<Position>,
+    model: RopeModel,
 }
 
 impl Rope {
-    fn new(n: usize) -> Self {
+    fn new(n: usize, model: RopeModel) -> Self {
         Rope {
             knots: vec![Position::new(); n],
+            model,
         }
     }
 
-    fn follow_knot(&mut self, knot_idx: usize) {
-        if knot_idx >= self.knots.len()
-            || self.knots[knot_idx - 1].is_touching(&self.knots[knot_idx])
-        {
-            return;
+    /// Moves every knot that is no longer touching the one in front of it towards it. Once a
+    /// knot stays put, so do all knots behind it.
+    fn follow_knots(&mut self) {
+        for idx in 1..self.knots.len() {
+            let moved = self.model.follow(&self.knots[idx - 1], &self.knots[idx]);
+            if moved == self.knots[idx] {
+                break;
+            }
+            self.knots[idx] = moved;
         }
+    }
 
-        if self.knots[knot_idx - 1].row == self.knots[knot_idx].row {
-            if self.knots[knot_idx - 1].col < self.knots[knot_idx].col {
-                // Go left
-                self.knots[knot_idx].col -= 1;
-            } else {
-                // Go right
-                self.knots[knot_idx].col += 1;
+    fn apply_direction(&mut self, d: Direction) {
+        let (row, col) = d.step();
+        self.knots[0].row += row;
+        self.knots[0].col += col;
+        self.follow_knots();
+    }
+}
+
+fn simulate(instructions: &[Instruction], n: usize, model: RopeModel) -> usize {
+    let mut tail_visited_pos: HashSet<Position> = HashSet::new();
+
+    let mut rope = Rope::new(n, model);
+    instructions.iter().for_each(|instruction| {
+        for _ in 0..instruction.n {
+            rope.apply_direction(instruction.direction);
+            tail_visited_pos.insert(*rope.knots.last().unwrap());
+        }
+    });
+
+    tail_visited_pos.len()
+}
+
+/// Every knot position at every step of a simulation. Frame 0 is the initial state, frame `i`
+/// the state after the `i`-th single step of the head.
+struct Trace {
+    frames: Vec<Vec<Position>>,
+}
+
+impl Trace {
+    fn record(instructions: &[Instruction], n: usize, model: RopeModel) -> Trace {
+        let mut rope = Rope::new(n, model);
+        let mut frames = vec![rope.knots.clone()];
+        for instruction in instructions {
+            for _ in 0..instruction.n {
+                rope.apply_direction(instruction.direction);
+                frames.push(rope.knots.clone());
             }
-        } else if self.knots[knot_idx - 1].col == self.knots[knot_idx].col {
-            if self.knots[knot_idx - 1].row < self.knots[knot_idx].row {
-                // Go up
-                self.knots[knot_idx].row -= 1;
+        }
+        Trace { frames }
+    }
+
+    fn steps(&self) -> usize {
+        self.frames.len() - 1
+    }
+
+    fn knots(&self) -> usize {
+        self.frames[0].len()
+    }
+
+    /// How often `knot` was at each position, counting the initial state.
+    fn visit_counts(&self, knot: usize) -> HashMap<Position, usize> {
+        let mut counts = HashMap::new();
+        for frame in &self.frames {
+            *counts.entry(frame[knot]).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn visited(&self, knot: usize) -> HashSet<Position> {
+        self.frames.iter().map(|frame| frame[knot]).collect()
+    }
+
+    /// Smallest `(top_left, bottom_right)` rectangle containing the start and every position
+    /// of every knot, so all renderings of a trace share the same grid.
+    fn bounds(&self) -> (Position, Position) {
+        let mut min = Position::new();
+        let mut max = Position::new();
+        for knot in self.frames.iter().flatten() {
+            min.row = min.row.min(knot.row);
+            min.col = min.col.min(knot.col);
+            max.row = max.row.max(knot.row);
+            max.col = max.col.max(knot.col);
+        }
+        (min, max)
+    }
+
+    fn render<F: Fn(Position) -> char>(&self, cell: F) -> String {
+        let (min, max) = self.bounds();
+        let mut out = String::new();
+        for row in min.row..=max.row {
+            out.extend((min.col..=max.col).map(|col| cell(Position { row, col })));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Draws the rope after `step` like the puzzle does: `H` for the head, the knot numbers
+    /// (or `T` for the tail of a two-knot rope), `s` for the start and `.` elsewhere. Where
+    /// knots overlap, the one closer to the head is drawn.
+    fn render_step(&self, step: usize) -> String {
+        let frame = &self.frames[step];
+        self.render(|pos| match frame.iter().position(|k| *k == pos) {
+            Some(idx) => knot_label(idx, frame.len()),
+            None if pos == Position::new() => 's',
+            None => '.',
+        })
+    }
+
+    /// Draws every cell `knot` visited as `#` and the start as `s`.
+    fn render_visited(&self, knot: usize) -> String {
+        let visited = self.visited(knot);
+        self.render(|pos| {
+            if pos == Position::new() {
+                's'
+            } else if visited.contains(&pos) {
+                '#'
             } else {
-                // Go down
-                self.knots[knot_idx].row += 1;
+                '.'
             }
-        } else {
-            if self.knots[knot_idx - 1].col < self.knots[knot_idx].col
-                && self.knots[knot_idx - 1].row < self.knots[knot_idx].row
-            {
-                // Go left-up
-                self.knots[knot_idx].row -= 1;
-                self.knots[knot_idx].col -= 1;
-            } else if self.knots[knot_idx - 1].col > self.knots[knot_idx].col
-                && self.knots[knot_idx - 1].row < self.knots[knot_idx].row
-            {
-                // Go right-up
-                self.knots[knot_idx].row -= 1;
-                self.knots[knot_idx].col += 1;
-            } else if self.knots[knot_idx - 1].col > self.knots[knot_idx].col
-                && self.knots[knot_idx - 1].row > self.knots[knot_idx].row
+        })
+    }
+
+    /// Draws how often `knot` was at every cell: `1` to `9`, `+` for more, `.` for never.
+    fn render_heatmap(&self, knot: usize) -> String {
+        let counts = self.visit_counts(knot);
+        self.render(|pos| match counts.get(&pos).copied().unwrap_or(0) {
+            0 => '.',
+            c @ 1..=9 => char::from_digit(c as u32, 10).unwrap(),
+            _ => '+',
+        })
+    }
+}
+
+/// `H` for the head, `T` for the tail of a two-knot rope, otherwise the knot's number, with
+/// letters from `a` on for knots beyond 9.
+fn knot_label(idx: usize, n: usize) -> char {
+    match idx {
+        0 => 'H',
+        1 if n == 2 => 'T',
+        1..=9 => char::from_digit(idx as u32, 10).unwrap(),
+        _ => char::from_digit((idx % 36) as u32, 36).unwrap(),
+    }
+}
+
+/// Reads the cells a tail should visit, one `x,y` pair per line with `x` growing to the right
+/// and `y` growing upwards, the start being `0,0`.
+fn load_targets(filename: &str) -> io::Result<Vec<Position>> {
+    read_lines(filename)?
+        .enumerate()
+        .map(|(idx, l)| {
+            let s = l?;
+            let err = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("line {}: invalid cell {:?}", idx + 1, s),
+                )
+            };
+            let (x, y) = s.trim().split_once(',').ok_or_else(err)?;
+            Ok(Position {
+                row: -y.trim().parse::<i32>().map_err(|_| err())?,
+                col: x.trim().parse::<i32>().map_err(|_| err())?,
+            })
+        })
+        .collect()
+}
+
+/// A rope during planning: its knots, how many targets the tail has visited so far, and the
+/// direction of the last head step, which decides whether the next step starts a new
+/// instruction.
+type PlanState = (Vec<Position>, usize, Option<Direction>);
+
+/// Finds the fewest head steps, and among those the fewest new instructions, after which the
+/// tail of a rope starting out as `knots` has visited `targets` in order. `last` is the
+/// direction of the head step before, which the first step can continue. Returns the head
+/// steps and where the knots end up.
+///
+/// An A* search over whole rope states. The estimate is the distance the tail still has to
+/// cover through the remaining targets, or the distance the head has to cover until the rope
+/// can reach the next target plus what the tail covers after that, whichever is larger, each
+/// divided by the longest a knot can move in one step. A `weight` above 1 scales the estimate
+/// up, which finds a plan with at most that many times the fewest steps much sooner. Returns
+/// `None` once more than `max_states` states would be needed.
+fn search_head_steps(
+    knots: &[Position],
+    last: Option<Direction>,
+    targets: &[Position],
+    model: RopeModel,
+    directions: &[Direction],
+    weight: usize,
+    max_states: usize,
+) -> io::Result<Option<(Vec<Direction>, Vec<Position>)>> {
+    let n = knots.len();
+    let metric = model.metric;
+    let longest_move = directions
+        .iter()
+        .map(|d| {
+            let (row, col) = d.step();
+            metric.distance(&Position::new(), &Position { row, col })
+        })
+        .max()
+        .unwrap_or(1) as usize;
+    let reach = (n - 1) * model.slack as usize;
+    // Distance the tail covers from each target on through the ones after it
+    let mut rest = vec![0; targets.len() + 1];
+    for idx in (1..targets.len()).rev() {
+        rest[idx - 1] = rest[idx] + metric.distance(&targets[idx - 1], &targets[idx]) as usize;
+    }
+    let estimate = |knots: &[Position], visited: usize| {
+        let Some(target) = targets.get(visited) else {
+            return 0;
+        };
+        let tail = metric.distance(&knots[n - 1], target) as usize;
+        let head = (metric.distance(&knots[0], target) as usize).saturating_sub(reach);
+        (tail.max(head) + rest[visited]).div_ceil(longest_move)
+    };
+    let visit = |knots: &[Position], mut visited: usize| {
+        while targets.get(visited) == Some(&knots[n - 1]) {
+            visited += 1;
+        }
+        visited
+    };
+
+    let start: PlanState = (knots.to_vec(), visit(knots, 0), last);
+    // Per state: the fewest steps and instructions to reach it, and the id it was last queued
+    // with. Per id: the state, and the id and head step it was reached from.
+    let mut costs: HashMap<PlanState, ((usize, usize), usize)> = HashMap::new();
+    let mut states: Vec<(PlanState, Option<(usize, Direction)>)> = Vec::new();
+    let mut queue = BinaryHeap::new();
+    costs.insert(start.clone(), ((0, 0), 0));
+    queue.push(Reverse((estimate(&start.0, start.1), 0, Reverse(0), 0)));
+    states.push((start, None));
+
+    let mut reached = None;
+    while let Some(Reverse((_, instructions, Reverse(steps), id))) = queue.pop() {
+        let state = &states[id].0;
+        if costs[state] != ((steps, instructions), id) {
+            continue;
+        }
+        if state.1 == targets.len() {
+            reached = Some(id);
+            break;
+        }
+
+        let (knots, visited, last) = state.clone();
+        for direction in directions {
+            let mut rope = Rope {
+                knots: knots.clone(),
+                model,
+            };
+            rope.apply_direction(*direction);
+            let next_visited = visit(&rope.knots, visited);
+            let next_cost = (
+                steps + 1,
+                instructions + usize::from(last != Some(*direction)),
+            );
+            let next: PlanState = (rope.knots, next_visited, Some(*direction));
+            if costs
+                .get(&next)
+                .is_some_and(|(known, _)| *known <= next_cost)
             {
-                // Go right-down
-                self.knots[knot_idx].row += 1;
-                self.knots[knot_idx].col += 1;
-            } else {
-                // Go left-down
-                self.knots[knot_idx].row += 1;
-                self.knots[knot_idx].col -= 1;
+                continue;
+            }
+            if states.len() >= max_states {
+                return Ok(None);
             }
+
+            // Ties go to the state with more steps, which is closer to the last target
+            let priority = next_cost.0 + weight * estimate(&next.0, next.1);
+            costs.insert(next.clone(), (next_cost, states.len()));
+            queue.push(Reverse((
+                priority,
+                next_cost.1,
+                Reverse(next_cost.0),
+                states.len(),
+            )));
+            states.push((next, Some((id, *direction))));
         }
+    }
 
-        self.follow_knot(knot_idx + 1);
+    let Some(mut id) = reached else {
+        return Err(io::Error::other(
+            "the tail cannot be pulled through the targets",
+        ));
+    };
+    let end = states[id].0 .0.clone();
+    let mut steps = Vec::new();
+    while let Some((parent, direction)) = states[id].1 {
+        steps.push(direction);
+        id = parent;
     }
+    steps.reverse();
+    Ok(Some((steps, end)))
+}
 
-    fn apply_direction(&mut self, d: Direction) {
-        match d {
-            Direction::Left => self.knots[0].col -= 1,
-            Direction::Up => self.knots[0].row -= 1,
-            Direction::Right => self.knots[0].col += 1,
-            Direction::Down => self.knots[0].row += 1,
+/// Finds head moves after which the tail of an `n` knot rope has visited `targets` in order.
+/// Also tells whether the plan is minimal.
+///
+/// The plan has the fewest head steps, and among those the fewest instructions, unless that
+/// takes more than `max_states` rope states, which happens quickly for long ropes since the
+/// search's estimate ignores that the head has to turn a taut rope around. The plan is then
+/// searched target by target with a weighted estimate, which keeps the head going straight
+/// but is not minimal.
+fn plan_tail_path(
+    targets: &[Position],
+    n: usize,
+    model: RopeModel,
+    directions: &[Direction],
+    max_states: usize,
+) -> io::Result<(Vec<Instruction>, bool)> {
+    let start = vec![Position::new(); n];
+    let (steps, minimal) =
+        match search_head_steps(&start, None, targets, model, directions, 1, max_states)? {
+            Some((steps, _)) => (steps, true),
+            None => {
+                let mut knots = start;
+                let mut steps: Vec<Direction> = Vec::new();
+                for target in targets {
+                    let (more, end) = search_head_steps(
+                        &knots,
+                        steps.last().copied(),
+                        std::slice::from_ref(target),
+                        model,
+                        directions,
+                        2,
+                        max_states,
+                    )?
+                    .ok_or_else(|| {
+                        io::Error::other(format!(
+                            "no plan found within {} rope states to pull the tail to {:?}, \
+                             raise --max-states",
+                            max_states, target
+                        ))
+                    })?;
+                    steps.extend(more);
+                    knots = end;
+                }
+                (steps, false)
+            }
+        };
+
+    let mut out: Vec<Instruction> = Vec::new();
+    for direction in steps {
+        match out.last_mut() {
+            Some(last) if last.direction == direction => last.n += 1,
+            _ => out.push(Instruction { direction, n: 1 }),
         }
-        self.follow_knot(1);
     }
+    Ok((out, minimal))
 }
 
-fn simulate(instructions: &Vec<Instruction>, n: usize) -> usize {
-    let mut tail_visited_pos: HashSet<Position> = HashSet::new();
-
-    let mut rope = Rope::new(n);
-    instructions.iter().for_each(|instruction| {
+/// Replays `instructions` on an `n` knot rope and checks that the tail visits `targets` in
+/// order.
+fn tail_visits(
+    instructions: &[Instruction],
+    n: usize,
+    model: RopeModel,
+    targets: &[Position],
+) -> bool {
+    let mut rope = Rope::new(n, model);
+    let mut remaining = targets.iter().peekable();
+    while remaining.next_if_eq(&&rope.knots[n - 1]).is_some() {}
+    for instruction in instructions {
         for _ in 0..instruction.n {
             rope.apply_direction(instruction.direction);
-            tail_visited_pos.insert(*rope.knots.last().unwrap());
+            while remaining.next_if_eq(&&rope.knots[n - 1]).is_some() {}
         }
-    });
+    }
+    remaining.peek().is_none()
+}
 
-    tail_visited_pos.len()
+fn task1(instructions: &[Instruction], model: RopeModel) -> usize {
+    simulate(instructions, 2, model)
+}
+
+fn task2(instructions: &[Instruction], model: RopeModel) -> usize {
+    simulate(instructions, 10, model)
+}
+
+fn check_knot(trace: &Trace, knot: usize) -> io::Result<()> {
+    if knot < trace.knots() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("the rope only has knots 0 to {}", trace.knots() - 1),
+        ))
+    }
 }
 
-fn task1(instructions: &Vec<Instruction>) -> usize {
-    simulate(instructions, 2)
+fn parse_number_arg(value: Option<String>, flag: &str) -> io::Result<usize> {
+    value.and_then(|v| v.parse::<usize>().ok()).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} expects a number", flag),
+        )
+    })
 }
 
-fn task2(instructions: &Vec<Instruction>) -> usize {
-    simulate(instructions, 10)
+fn parse_slack(value: Option<String>) -> io::Result<i32> {
+    value
+        .and_then(|v| v.parse::<i32>().ok())
+        .filter(|v| *v >= 0)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "--slack expects a non-negative number",
+            )
+        })
+}
+
+fn parse_metric(value: Option<String>) -> io::Result<Metric> {
+    match value.as_deref() {
+        Some("chebyshev") => Ok(Metric::Chebyshev),
+        Some("manhattan") => Ok(Metric::Manhattan),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "--touch expects chebyshev or manhattan",
+        )),
+    }
 }
 
 fn main() -> io::Result<()> {
-    let data = load_data("input.txt")?;
+    // Usage: day09 [path] [--knots N] [--slack N] [--touch chebyshev|manhattan] [--step S]
+    //              [--visited K] [--heatmap K]
+    //        day09 --plan TARGETS [--knots N] [--slack N] [--touch ...] [--diagonal]
+    //                             [--max-states N]
+    let mut path = String::from("input.txt");
+    let mut knots = None;
+    let mut step = None;
+    let mut visited = None;
+    let mut heatmap = None;
+    let mut model = RopeModel::default();
+    let mut plan = None;
+    let mut diagonal = false;
+    let mut max_states = 1_000_000;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--knots" => knots = Some(parse_number_arg(args.next(), "--knots")?),
+            "--slack" => model.slack = parse_slack(args.next())?,
+            "--touch" => model.metric = parse_metric(args.next())?,
+            "--step" => step = Some(parse_number_arg(args.next(), "--step")?),
+            "--visited" => visited = Some(parse_number_arg(args.next(), "--visited")?),
+            "--heatmap" => heatmap = Some(parse_number_arg(args.next(), "--heatmap")?),
+            "--plan" => {
+                plan =
+                    Some(args.next().ok_or_else(|| {
+                        Error::new(ErrorKind::InvalidInput, "--plan expects a path")
+                    })?)
+            }
+            "--diagonal" => diagonal = true,
+            "--max-states" => max_states = parse_number_arg(args.next(), "--max-states")?,
+            _ => path = arg,
+        }
+    }
+
+    if knots == Some(0) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "a rope needs at least one knot",
+        ));
+    }
+
+    if let Some(targets_path) = plan {
+        let targets = load_targets(&targets_path)?;
+        let n = knots.unwrap_or(10);
+        let directions: &[Direction] = if diagonal {
+            &Direction::ALL
+        } else {
+            &Direction::CARDINAL
+        };
+
+        let (instructions, minimal) = plan_tail_path(&targets, n, model, directions, max_states)?;
+        if !minimal {
+            eprintln!(
+                "No minimal plan within {} rope states (raise --max-states), \
+                 the moves below are not minimal",
+                max_states
+            );
+        }
+        if !tail_visits(&instructions, n, model, &targets) {
+            return Err(io::Error::other("the planned moves miss a target cell"));
+        }
+        for instruction in instructions {
+            println!("{}", instruction);
+        }
+        return Ok(());
+    }
+
+    let data = load_data(&path)?;
+
+    if step.is_some() || visited.is_some() || heatmap.is_some() {
+        let trace = Trace::record(&data, knots.unwrap_or(10), model);
+        if let Some(s) = step {
+            if s > trace.steps() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the instructions only have {} steps", trace.steps()),
+                ));
+            }
+            println!("== Step {} ==\n{}", s, trace.render_step(s));
+        }
+        if let Some(knot) = visited {
+            check_knot(&trace, knot)?;
+            println!(
+                "Knot {}: {} positions\n{}",
+                knot,
+                trace.visited(knot).len(),
+                trace.render_visited(knot)
+            );
+        }
+        if let Some(knot) = heatmap {
+            check_knot(&trace, knot)?;
+            println!(
+                "Knot {}: {} steps\n{}",
+                knot,
+                trace.steps(),
+                trace.render_heatmap(knot)
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(n) = knots {
+        println!(
+            "Tail positions with {} knots: {}",
+            n,
+            simulate(&data, n, model)
+        );
+        return Ok(());
+    }
 
     // Task 1
-    let out_task1 = task1(&data);
+    let out_task1 = task1(&data, model);
     println!("Task 1: {}", out_task1);
 
     // Task 2
-    let out_task2 = task2(&data);
+    let out_task2 = task2(&data, model);
     println!("Task 2: {}", out_task2);
 
     Ok(())
@@ -1,3 +1,7 @@
+#[path = "../../day04/src/interval.rs"]
+mod interval;
+
+use interval::{Interval, IntervalSet};
 use regex::Regex;
 use std::cmp;
 use std::collections::HashSet;
@@ -79,29 +83,6 @@ fn task1(data: &Vec<Sensor>) -> usize {
     beacon_empty_locations.len()
 }
 
-fn merge_intervals(mut intervals: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
-    intervals.sort();
-
-    let mut out = Vec::new();
-
-    let mut left = 0;
-    let mut right = intervals[0].1;
-
-    for i in 1..intervals.len() {
-        if (right + 1) < intervals[i].0 {
-            out.push((left, right));
-            left = intervals[i].0;
-            right = intervals[i].1;
-        } else {
-            right = cmp::max(right, intervals[i].1);
-        }
-    }
-
-    out.push((left, right));
-
-    out
-}
-
 fn task2(data: &Vec<Sensor>) -> usize {
     let lb: i32 = 0;
     // let ub: i32 = 20;
@@ -121,7 +102,10 @@ fn task2(data: &Vec<Sensor>) -> usize {
             let y_interval_left = cmp::max(lb, sensor.location.1 - remaining_dist);
             let y_interval_right = cmp::min(ub, sensor.location.1 + remaining_dist);
 
-            row_intervals[x as usize].push((y_interval_left, y_interval_right));
+            // The y-range is empty if the sensor's reach lies entirely outside the bounds
+            if let Some(interval) = Interval::new(y_interval_left, y_interval_right) {
+                row_intervals[x as usize].push(interval);
+            }
         }
     }
 
@@ -129,14 +113,14 @@ fn task2(data: &Vec<Sensor>) -> usize {
     let mut target_x = 0;
     let mut target_y = 0;
     for i in 0..row_intervals.len() {
-        let merged_intervals = merge_intervals(row_intervals[i].clone());
+        let merged_intervals = IntervalSet::from_iter(row_intervals[i].iter().copied());
 
-        if merged_intervals.len() > 1 {
+        if merged_intervals.intervals().len() > 1 {
             // We found it!
             target_x = i;
 
-            assert!(merged_intervals.len() == 2);
-            target_y = (merged_intervals[0].1 + 1) as usize;
+            assert!(merged_intervals.intervals().len() == 2);
+            target_y = (merged_intervals.intervals()[0].end + 1) as usize;
 
             break;
         }